@@ -44,6 +44,17 @@ pub enum AnyClientState {
     Mock(MockClientState),
 }
 
+impl AnyClientState {
+    /// Returns true if the client is frozen
+    pub fn is_frozen(&self) -> bool {
+        match self {
+            AnyClientState::Tendermint(cs) => cs.inner().is_frozen(),
+            #[cfg(feature = "testing")]
+            AnyClientState::Mock(cs) => cs.frozen_height.is_some(),
+        }
+    }
+}
+
 impl From<TmClientState> for AnyClientState {
     fn from(cs: TmClientState) -> Self {
         Self::Tendermint(cs)
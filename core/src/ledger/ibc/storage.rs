@@ -230,7 +230,7 @@ pub fn client_id(key: &Key) -> Result<ClientId> {
 }
 
 /// Returns the height from the given consensus state key
-/// `#IBC/clients/<client_id>/consensusState/0-<height>`
+/// `#IBC/clients/<client_id>/consensusStates/<rev_number>-<rev_height>`
 pub fn consensus_height(key: &Key) -> Result<Height> {
     match &key.segments[..] {
         [
@@ -448,3 +448,32 @@ pub fn is_ibc_denom_key(key: &Key) -> Option<(String, String)> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::ibc::core::host::types::identifiers::ClientType;
+
+    proptest! {
+        /// Test that the height in a consensus state key is parsed back
+        /// with the same revision number and revision height
+        #[test]
+        fn test_consensus_height_roundtrip(
+            revision_number in any::<u64>(),
+            revision_height in 1..=u64::MAX,
+            counter in any::<u64>(),
+        ) {
+            let client_type = ClientType::new("07-tendermint").unwrap();
+            let id = ClientId::new(client_type, counter).unwrap();
+            let height = Height::new(revision_number, revision_height).unwrap();
+            let key = consensus_state_key(&id, height);
+
+            prop_assert_eq!(consensus_height(&key).unwrap(), height);
+            prop_assert_eq!(client_id(&key).unwrap(), id.clone());
+            let prefix = consensus_state_prefix(&id);
+            prop_assert!(key.split_prefix(&prefix).is_some());
+        }
+    }
+}
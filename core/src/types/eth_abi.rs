@@ -1,16 +1,42 @@
 //! This module defines encoding methods compatible with Ethereum
 //! smart contracts.
 
+use std::io::Write;
 use std::marker::PhantomData;
 
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use data_encoding::HEXLOWER_PERMISSIVE;
+use ethabi::param_type::{ParamType, Reader};
 #[doc(inline)]
 pub use ethabi::token::Token;
+use thiserror::Error;
 
 use crate::proto::{Signable, SignableEthMessage};
 use crate::types::keccak::{keccak_hash, KeccakHash};
 
+/// Errors for handling Ethereum ABI-encoded data
+#[allow(missing_docs)]
+#[derive(Error, Debug)]
+pub enum EthAbiError {
+    #[error("Invalid Solidity signature: {0}")]
+    InvalidSignature(String),
+    #[error("Token mismatch: {0}")]
+    TokenMismatch(String),
+    #[error("Invalid Solidity enum: {0}")]
+    InvalidEnum(String),
+    #[error("Invalid Merkle proof: {0}")]
+    InvalidMerkleProof(String),
+    #[error("Integer overflow: {0}")]
+    IntegerOverflow(String),
+    #[error("Invalid hex string: {0}")]
+    InvalidHex(String),
+}
+
 /// A container for data types that are able to be Ethereum ABI-encoded.
+///
+/// A cell is serialized with Borsh as its encoded data only, without any
+/// information about `T`. Like [`EncodeCell::new_from`], deserializing a
+/// cell doesn't check that its data is an encoded value of `T`.
 #[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema)]
 #[repr(transparent)]
 pub struct EncodeCell<T: ?Sized> {
@@ -79,6 +105,44 @@ impl<T> EncodeCell<T> {
     pub fn into_inner(self) -> Vec<u8> {
         self.encoded_data
     }
+
+    /// Return the ABI encoded value as a `0x`-prefixed lowercase hex
+    /// string.
+    pub fn to_hex(&self) -> String {
+        format!("0x{}", HEXLOWER_PERMISSIVE.encode(&self.encoded_data))
+    }
+
+    /// Parse an ABI encoded value of type `T` from a hex string, with or
+    /// without the `0x` prefix. Like [`EncodeCell::new_from`], the encoded
+    /// data isn't checked against `T`.
+    pub fn from_hex(hex: &str) -> Result<Self, EthAbiError> {
+        let digits = hex.strip_prefix("0x").unwrap_or(hex);
+        let encoded_data = HEXLOWER_PERMISSIVE
+            .decode(digits.as_bytes())
+            .map_err(|e| EthAbiError::InvalidHex(format!("{hex}: {e}")))?;
+        Ok(Self {
+            encoded_data,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Checks if both cells encode the same tokens of the given parameter
+    /// types, ignoring byte differences which don't change the decoded
+    /// values, such as trailing padding. A cell which can't be decoded
+    /// isn't equivalent to any other.
+    pub fn abi_eq(
+        &self,
+        other: &EncodeCell<T>,
+        param_types: &[ParamType],
+    ) -> bool {
+        match (
+            ethabi::decode(param_types, &self.encoded_data),
+            ethabi::decode(param_types, &other.encoded_data),
+        ) {
+            (Ok(tokens), Ok(other_tokens)) => tokens == other_tokens,
+            _ => false,
+        }
+    }
 }
 
 /// Contains a method to encode data to a format compatible with Ethereum.
@@ -92,6 +156,37 @@ pub trait Encode<const N: usize>: Sized {
         EncodeCell::new(self)
     }
 
+    /// Returns the ABI encoded value of `self`, preceded by its length.
+    ///
+    /// The first 32 bytes are a big-endian `uint256` holding the byte
+    /// length of the payload, followed by the payload itself, as returned
+    /// by [`Encode::encode`]. Note that this length prefix is not part of
+    /// the standard ABI, which only embeds lengths of dynamic types.
+    fn encode_length_prefixed(&self) -> Vec<u8> {
+        let payload = self.encode().into_inner();
+        let mut encoded =
+            ethabi::encode(&[Token::Uint(ethabi::Uint::from(payload.len()))]);
+        encoded.extend(payload);
+        encoded
+    }
+
+    /// Returns the calldata of a call to the function with the given
+    /// Solidity signature, equivalent to `abi.encodeWithSignature`.
+    ///
+    /// The calldata is the function selector of the signature, followed
+    /// by the ABI encoded value of `self`. The parameters of the signature
+    /// have to match the tokens of `self`.
+    fn encode_with_signature(
+        &self,
+        signature: &str,
+    ) -> Result<Vec<u8>, EthAbiError> {
+        let tokens = self.tokenize();
+        validate_against_signature(&tokens, signature)?;
+        let mut calldata = function_selector(signature).to_vec();
+        calldata.extend(ethabi::encode(&tokens));
+        Ok(calldata)
+    }
+
     /// Encodes a slice of [`Token`] instances, and returns the
     /// keccak hash of the encoded string.
     fn keccak256(&self) -> KeccakHash {
@@ -107,6 +202,242 @@ pub trait Encode<const N: usize>: Sized {
     }
 }
 
+/// Contains a method to ABI encode data directly into a writer.
+///
+/// This yields the same bytes as [`Encode::encode`]. The tokens of the
+/// message are still built, but the encoded bytes are written out as they
+/// are produced instead of being collected into one output buffer, which
+/// is useful for large messages, such as validator set updates.
+pub trait EncodeStreaming<const N: usize>: Encode<N> {
+    /// Writes the ABI encoded value of `self` to `writer`.
+    fn encode_to_writer<W: Write>(
+        &self,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        encode_to_writer(&self.tokenize(), writer)
+    }
+}
+
+impl<T: Encode<N>, const N: usize> EncodeStreaming<N> for T {}
+
+/// Writes the ABI encoding of a sequence of [`Token`] instances to
+/// `writer`, equivalent to `ethabi::encode`.
+pub fn encode_to_writer<W: Write>(
+    tokens: &[Token],
+    writer: &mut W,
+) -> std::io::Result<()> {
+    write_sequence(tokens, writer)
+}
+
+/// The size in bytes of an ABI word.
+const WORD_LEN: usize = 32;
+
+/// Returns the number of bytes `len` bytes take up, once padded to a
+/// multiple of the ABI word size.
+const fn padded_len(len: usize) -> usize {
+    (len + WORD_LEN - 1) / WORD_LEN * WORD_LEN
+}
+
+/// Returns the size of the head of a token in an ABI encoded sequence.
+fn head_len(token: &Token) -> usize {
+    match token {
+        _ if token.is_dynamic() => WORD_LEN,
+        Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+            tokens.iter().map(head_len).sum()
+        }
+        _ => WORD_LEN,
+    }
+}
+
+/// Returns the size of the full ABI encoding of a token.
+fn encoded_len(token: &Token) -> usize {
+    match token {
+        Token::Bytes(bytes) => WORD_LEN + padded_len(bytes.len()),
+        Token::String(string) => WORD_LEN + padded_len(string.len()),
+        Token::Array(tokens) => WORD_LEN + sequence_len(tokens),
+        Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+            sequence_len(tokens)
+        }
+        _ => WORD_LEN,
+    }
+}
+
+/// Returns the size of the ABI encoding of a sequence of tokens.
+fn sequence_len(tokens: &[Token]) -> usize {
+    tokens
+        .iter()
+        .map(|token| {
+            if token.is_dynamic() {
+                WORD_LEN + encoded_len(token)
+            } else {
+                head_len(token)
+            }
+        })
+        .sum()
+}
+
+/// Writes a `uint256` word holding the given value.
+fn write_uint<W: Write>(value: usize, writer: &mut W) -> std::io::Result<()> {
+    write_word(ethabi::Uint::from(value), writer)
+}
+
+/// Writes a 32 byte big-endian word.
+fn write_word<W: Write>(
+    value: ethabi::Uint,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    let mut word = [0u8; WORD_LEN];
+    value.to_big_endian(&mut word);
+    writer.write_all(&word)
+}
+
+/// Writes the given bytes, right padded with zeroes to a multiple of the
+/// ABI word size.
+fn write_right_padded<W: Write>(
+    bytes: &[u8],
+    writer: &mut W,
+) -> std::io::Result<()> {
+    writer.write_all(bytes)?;
+    let padding = [0u8; WORD_LEN];
+    writer.write_all(&padding[..padded_len(bytes.len()) - bytes.len()])
+}
+
+/// Writes the heads of a sequence of tokens, followed by the tails of
+/// its dynamic tokens.
+fn write_sequence<W: Write>(
+    tokens: &[Token],
+    writer: &mut W,
+) -> std::io::Result<()> {
+    let mut offset: usize = tokens.iter().map(head_len).sum();
+    for token in tokens {
+        if token.is_dynamic() {
+            write_uint(offset, writer)?;
+            offset += encoded_len(token);
+        } else {
+            write_token(token, writer)?;
+        }
+    }
+    for token in tokens.iter().filter(|token| token.is_dynamic()) {
+        write_token(token, writer)?;
+    }
+    Ok(())
+}
+
+/// Writes the full ABI encoding of a token.
+fn write_token<W: Write>(token: &Token, writer: &mut W) -> std::io::Result<()> {
+    match token {
+        Token::Address(address) => {
+            writer.write_all(&[0u8; WORD_LEN - 20])?;
+            writer.write_all(address.as_bytes())
+        }
+        Token::FixedBytes(bytes) => write_right_padded(bytes, writer),
+        Token::Bytes(bytes) => {
+            write_uint(bytes.len(), writer)?;
+            write_right_padded(bytes, writer)
+        }
+        Token::String(string) => {
+            write_uint(string.len(), writer)?;
+            write_right_padded(string.as_bytes(), writer)
+        }
+        Token::Int(value) | Token::Uint(value) => write_word(*value, writer),
+        Token::Bool(value) => write_uint(usize::from(*value), writer),
+        Token::Array(tokens) => {
+            write_uint(tokens.len(), writer)?;
+            write_sequence(tokens, writer)
+        }
+        Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+            write_sequence(tokens, writer)
+        }
+    }
+}
+
+/// Contains a method to encode a Rust enum like a Solidity enum, i.e. as
+/// the `uint8` discriminant of its variant.
+pub trait EncodeEnum {
+    /// Returns the discriminant of the variant of `self`, which must be
+    /// its index in the Solidity enum.
+    fn discriminant(&self) -> usize;
+
+    /// Encodes the discriminant of `self` as a `uint8` [`Token`].
+    fn tokenize_enum(&self) -> Result<Token, EthAbiError> {
+        let discriminant = self.discriminant();
+        if discriminant > u8::MAX as usize {
+            return Err(EthAbiError::InvalidEnum(format!(
+                "The discriminant {discriminant} doesn't fit in a uint8"
+            )));
+        }
+        Ok(Token::Uint(ethabi::Uint::from(discriminant)))
+    }
+}
+
+/// Contains the methods to hash a struct according to EIP-712, such that
+/// the signatures over it are compatible with wallets.
+///
+/// See <https://eips.ethereum.org/EIPS/eip-712>.
+pub trait Eip712 {
+    /// Returns the encoded type of the struct, such as
+    /// `Mail(Person from,Person to,string contents)Person(string name,address
+    /// wallet)`, where the referenced struct types are appended in
+    /// alphabetical order.
+    fn encode_type() -> String;
+
+    /// Returns the keccak hash of the encoded type of the struct.
+    fn type_hash() -> KeccakHash {
+        keccak_hash(Self::encode_type())
+    }
+
+    /// Returns the encoded members of the struct, in the order of the
+    /// encoded type, each one as a 32-byte word. Dynamic values, i.e.
+    /// `bytes` and `string`, are encoded as their keccak hash, and structs
+    /// as their [`Eip712::struct_hash`].
+    fn encode_data(&self) -> Vec<u8>;
+
+    /// Returns the EIP-712 hash of the struct, i.e. the keccak hash of
+    /// its type hash followed by its encoded data.
+    fn struct_hash(&self) -> KeccakHash {
+        let KeccakHash(type_hash) = Self::type_hash();
+        let mut bytes = type_hash.to_vec();
+        bytes.extend(self.encode_data());
+        keccak_hash(bytes)
+    }
+}
+
+/// Returns the EIP-712 digest to be signed for a struct, given its hash
+/// and the separator of the signing domain.
+pub fn eip712_digest(
+    domain_separator: &KeccakHash,
+    struct_hash: &KeccakHash,
+) -> KeccakHash {
+    let mut bytes = vec![0x19, 0x01];
+    bytes.extend(domain_separator.0);
+    bytes.extend(struct_hash.0);
+    keccak_hash(bytes)
+}
+
+/// Returns the EIP-712 separator of the signing domain of the Ethereum
+/// bridge contract, i.e. the hash of an `EIP712Domain` struct with the
+/// name, version, chain ID and address of the contract.
+pub fn bridge_domain_separator(
+    name: &str,
+    version: &str,
+    chain_id: u64,
+    contract: [u8; 20],
+) -> KeccakHash {
+    let KeccakHash(type_hash) = keccak_hash(
+        "EIP712Domain(string name,string version,uint256 chainId,address \
+         verifyingContract)",
+    );
+    let [verifying_contract] = contract.tokenize();
+    let encoded = ethabi::encode(&[
+        Token::FixedBytes(type_hash.to_vec()),
+        Token::FixedBytes(keccak_hash(name).0.to_vec()),
+        Token::FixedBytes(keccak_hash(version).0.to_vec()),
+        Token::Uint(chain_id.into()),
+        verifying_contract,
+    ]);
+    keccak_hash(encoded)
+}
+
 /// Represents an Ethereum encoding method equivalent
 /// to `abi.encode`.
 pub type AbiEncode<const N: usize> = [Token; N];
@@ -118,18 +449,408 @@ impl<const N: usize> Encode<N> for AbiEncode<N> {
     }
 }
 
+/// Encodes an array of values as a fixed-size Solidity array `T[M]`.
+///
+/// Unlike a dynamic array `T[]`, a fixed-size array of static elements is
+/// encoded in place, without an offset nor a length.
+impl<T, const M: usize> Encode<1> for [T; M]
+where
+    T: Encode<1>,
+{
+    fn tokenize(&self) -> [Token; 1] {
+        let elements = self.iter().map(|element| {
+            let [token] = element.tokenize();
+            token
+        });
+        [Token::FixedArray(elements.collect())]
+    }
+}
+
+/// Encodes a raw 20-byte array as a Solidity `address`.
+impl Encode<1> for [u8; 20] {
+    fn tokenize(&self) -> [Token; 1] {
+        [Token::Address(ethabi::Address::from(*self))]
+    }
+}
+
+/// Encodes a [`Duration`] as a Solidity `uint64` holding a number of
+/// seconds, e.g. for bridge timeouts.
+///
+/// Sub-second precision is truncated. Since [`Duration::as_secs`] already
+/// returns a `u64`, every duration fits and the encoding cannot fail.
+///
+/// [`Duration`]: std::time::Duration
+/// [`Duration::as_secs`]: std::time::Duration::as_secs
+impl Encode<1> for std::time::Duration {
+    fn tokenize(&self) -> [Token; 1] {
+        [Token::Uint(self.as_secs().into())]
+    }
+}
+
+/// A dynamically sized byte array, encoded as a Solidity `bytes` value.
+///
+/// Unlike [`Token::FixedBytes`], which is encoded in place and padded to
+/// a single 32-byte word (Solidity `bytes1` to `bytes32`), a `bytes` value
+/// is stored in the tail of the encoding, preceded by its length, and
+/// right padded to a multiple of 32 bytes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DynBytes(pub Vec<u8>);
+
+impl From<Vec<u8>> for DynBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl Encode<1> for DynBytes {
+    fn tokenize(&self) -> [Token; 1] {
+        [Token::Bytes(self.0.clone())]
+    }
+}
+
+/// A Merkle proof of a single leaf, encoded as a Solidity
+/// `(bytes32[] proof, uint256 index)` pair to be passed to a verifier
+/// contract on Ethereum.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EthMerkleProof {
+    /// The sibling hashes, from the leaf up to the root
+    pub siblings: Vec<KeccakHash>,
+    /// The index of the leaf, whose bits give the side of each sibling
+    pub index: u64,
+}
+
+impl EthMerkleProof {
+    /// Creates a proof from its raw sibling hashes, each of which has to be
+    /// 32 bytes long.
+    pub fn from_siblings<S>(
+        siblings: impl IntoIterator<Item = S>,
+        index: u64,
+    ) -> Result<Self, EthAbiError>
+    where
+        S: AsRef<[u8]>,
+    {
+        let siblings = siblings
+            .into_iter()
+            .enumerate()
+            .map(|(position, sibling)| {
+                let sibling = sibling.as_ref();
+                KeccakHash::try_from(sibling).map_err(|_| {
+                    EthAbiError::InvalidMerkleProof(format!(
+                        "The sibling {position} is {} bytes long instead of 32",
+                        sibling.len()
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { siblings, index })
+    }
+}
+
+impl Encode<2> for EthMerkleProof {
+    fn tokenize(&self) -> [Token; 2] {
+        let [proof] = self.siblings.as_slice().tokenize();
+        [proof, Token::Uint(self.index.into())]
+    }
+}
+
+/// Commitments at some heights, encoded as a Solidity
+/// `(uint256[] heights, bytes32[] commitments)` pair of parallel arrays to
+/// be submitted to Ethereum in a batch.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BatchedCommitments {
+    heights: Vec<u64>,
+    commitments: Vec<KeccakHash>,
+}
+
+impl BatchedCommitments {
+    /// Creates a batch from the heights and the commitments at these
+    /// heights, which have to be of the same length.
+    pub fn new(
+        heights: Vec<u64>,
+        commitments: Vec<KeccakHash>,
+    ) -> Result<Self, EthAbiError> {
+        if heights.len() != commitments.len() {
+            return Err(EthAbiError::TokenMismatch(format!(
+                "The batch has {} heights but {} commitments",
+                heights.len(),
+                commitments.len()
+            )));
+        }
+        Ok(Self {
+            heights,
+            commitments,
+        })
+    }
+
+    /// Returns the heights of the commitments
+    pub fn heights(&self) -> &[u64] {
+        &self.heights
+    }
+
+    /// Returns the commitments, in the order of their heights
+    pub fn commitments(&self) -> &[KeccakHash] {
+        &self.commitments
+    }
+}
+
+impl Encode<2> for BatchedCommitments {
+    fn tokenize(&self) -> [Token; 2] {
+        let heights = self
+            .heights
+            .iter()
+            .map(|height| Token::Uint((*height).into()))
+            .collect();
+        let [commitments] = self.commitments.as_slice().tokenize();
+        [Token::Array(heights), commitments]
+    }
+}
+
+/// A client upgrade approved by governance, encoded as a Solidity
+/// `(uint256 height, bytes clientState, bytes consensusState)` struct to
+/// be relayed to Ethereum.
+///
+/// The states are the Protobuf encodings of the upgraded client and
+/// consensus states, wrapped in an `Any`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientUpgradeAbi {
+    /// The height at which the client is upgraded
+    pub height: u64,
+    /// The Protobuf bytes of the upgraded client state
+    pub client_state: Vec<u8>,
+    /// The Protobuf bytes of the upgraded consensus state
+    pub consensus_state: Vec<u8>,
+}
+
+impl Encode<3> for ClientUpgradeAbi {
+    fn tokenize(&self) -> [Token; 3] {
+        [
+            Token::Uint(self.height.into()),
+            Token::Bytes(self.client_state.clone()),
+            Token::Bytes(self.consensus_state.clone()),
+        ]
+    }
+}
+
+/// An IBC packet commitment, encoded as a Solidity `(uint64 sequence,
+/// (uint64 revisionNumber, uint64 revisionHeight) timeoutHeight, uint64
+/// timeoutTimestamp, bytes32 dataHash)` struct for the counterparty
+/// contract on Ethereum.
+///
+/// All the members are static, so the struct is encoded in place.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PacketCommitmentAbi {
+    /// The sequence number of the packet
+    pub sequence: u64,
+    /// The revision number and the revision height of the timeout height
+    pub timeout_height: (u64, u64),
+    /// The timeout timestamp, in nanoseconds since the Unix epoch
+    pub timeout_timestamp: u64,
+    /// The hash of the packet data
+    pub data_hash: [u8; 32],
+}
+
+impl Encode<4> for PacketCommitmentAbi {
+    fn tokenize(&self) -> [Token; 4] {
+        let (revision_number, revision_height) = self.timeout_height;
+        [
+            Token::Uint(self.sequence.into()),
+            Token::Tuple(vec![
+                Token::Uint(revision_number.into()),
+                Token::Uint(revision_height.into()),
+            ]),
+            Token::Uint(self.timeout_timestamp.into()),
+            Token::FixedBytes(self.data_hash.to_vec()),
+        ]
+    }
+}
+
+/// A 128-bit value, encoded as two `uint256` words holding its 64-bit
+/// halves, for contracts storing 128-bit values as two 64-bit words.
+///
+/// The high word comes first, followed by the low word, such that the
+/// original value is `high << 64 | low`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SplitU128(pub u128);
+
+impl Encode<2> for SplitU128 {
+    fn tokenize(&self) -> [Token; 2] {
+        let high = (self.0 >> 64) as u64;
+        let low = self.0 as u64;
+        [Token::Uint(high.into()), Token::Uint(low.into())]
+    }
+}
+
+/// Encodes an optional value as a Solidity `(bool, T)` presence tuple.
+///
+/// The first token flags whether a value is present. When it is absent,
+/// the second token is the tokenization of `T::default()`, which for the
+/// integer and hash types we encode amounts to a zeroed 32-byte slot.
+impl<T> Encode<2> for Option<T>
+where
+    T: Encode<1> + Default,
+{
+    fn tokenize(&self) -> [Token; 2] {
+        let (is_some, [value]) = match self {
+            Some(value) => (true, value.tokenize()),
+            None => (false, T::default().tokenize()),
+        };
+        [Token::Bool(is_some), value]
+    }
+}
+
+/// Encodes a signed amount as a Solidity `int256`, in two's complement.
+///
+/// The value is clamped to the bounds of `int256` instead of panicking on
+/// overflow. An `i128` always fits, but the guard keeps the encoding sound
+/// if the input type is ever widened.
+pub fn encode_signed_saturating(value: i128) -> Token {
+    let magnitude = ethabi::Uint::from(value.unsigned_abs());
+    // The bounds of `int256` are `-2^255` and `2^255 - 1`
+    let max = ethabi::Uint::MAX >> 1;
+    let word = if value >= 0 {
+        magnitude.min(max)
+    } else {
+        let magnitude = magnitude.min(max + 1);
+        (!magnitude).overflowing_add(ethabi::Uint::one()).0
+    };
+    Token::Int(word)
+}
+
+/// Encodes an unsigned integer of any width, given by its big-endian bytes,
+/// as a Solidity `uint256`.
+///
+/// The leading zero bytes are ignored, so an integer stored in more than
+/// 32 bytes is accepted as long as its value fits in 256 bits.
+pub fn encode_uint_be(bytes: &[u8]) -> Result<Token, EthAbiError> {
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    let significant = &bytes[start..];
+    if significant.len() > 32 {
+        return Err(EthAbiError::IntegerOverflow(format!(
+            "The integer needs {} bytes, more than the 32 bytes of a uint256",
+            significant.len()
+        )));
+    }
+    Ok(Token::Uint(ethabi::Uint::from_big_endian(significant)))
+}
+
+/// Returns the selector of a Solidity function, i.e. the first 4 bytes of
+/// the keccak hash of its signature, such as `transfer(address,uint256)`.
+pub fn function_selector(signature: &str) -> [u8; 4] {
+    let KeccakHash(hash) = keccak_hash(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Returns the topic of a Solidity event, i.e. the `topic0` of the logs
+/// it emits, such as for `Transfer(address,address,uint256)`.
+///
+/// The topic is the full keccak hash of the event signature, whereas
+/// [`function_selector`] only keeps the first 4 bytes of the hash of the
+/// function signature.
+pub fn event_topic(signature: &str) -> KeccakHash {
+    keccak_hash(signature.as_bytes())
+}
+
+/// Returns the hash of a message signed with the Ethereum personal-sign
+/// scheme, i.e. `keccak256("\x19Ethereum Signed Message:\n" ++ len ++
+/// message)`, with the length of the message written in decimal.
+///
+/// For a 32-byte message, such as a hash, this is the same as
+/// [`Encode::signable_keccak256`] over the hash.
+pub fn eth_signed_message_hash(message: &[u8]) -> KeccakHash {
+    let mut eth_message =
+        format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+    eth_message.extend_from_slice(message);
+    keccak_hash(eth_message)
+}
+
+/// Checks that the given tokens match the parameter types of a Solidity
+/// function signature, such as `transfer(address,uint256)`.
+pub fn validate_against_signature(
+    tokens: &[Token],
+    signature: &str,
+) -> Result<(), EthAbiError> {
+    let param_types = parse_signature_params(signature)?;
+    if param_types.len() != tokens.len() {
+        return Err(EthAbiError::TokenMismatch(format!(
+            "Expected {} tokens for {signature}, got {}",
+            param_types.len(),
+            tokens.len()
+        )));
+    }
+    for (index, (token, param_type)) in
+        tokens.iter().zip(param_types.iter()).enumerate()
+    {
+        if !token.type_check(param_type) {
+            return Err(EthAbiError::TokenMismatch(format!(
+                "Token {index} of {signature} should be a {param_type}, got \
+                 {token:?}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Parses the parameter types of a Solidity function signature.
+fn parse_signature_params(
+    signature: &str,
+) -> Result<Vec<ParamType>, EthAbiError> {
+    let params = signature
+        .find('(')
+        .filter(|_| signature.ends_with(')'))
+        .map(|start| &signature[start + 1..signature.len() - 1])
+        .ok_or_else(|| EthAbiError::InvalidSignature(signature.to_owned()))?;
+    split_params(params)
+        .into_iter()
+        .map(|param| {
+            Reader::read(param).map_err(|e| {
+                EthAbiError::InvalidSignature(format!(
+                    "{signature}: parameter {param}: {e}"
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Splits a comma-separated list of parameter types, leaving the
+/// commas nested in tuple types untouched.
+fn split_params(params: &str) -> Vec<&str> {
+    if params.trim().is_empty() {
+        return vec![];
+    }
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut split = vec![];
+    for (index, c) in params.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                split.push(params[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    split.push(params[start..].trim());
+    split
+}
+
 // TODO: test signatures here once we merge secp keys
 #[cfg(test)]
 mod tests {
     use std::convert::TryInto;
     use std::str::FromStr;
 
+    use borsh_ext::BorshSerializeExt;
     use data_encoding::HEXLOWER;
     use ethabi::ethereum_types::U256;
     use tiny_keccak::{Hasher, Keccak};
 
     use super::*;
-    use crate::types::ethereum_events::EthAddress;
+    use crate::types::ethereum_events::{EthAddress, Uint};
     use crate::types::vote_extensions::validator_set_update::ValidatorSetArgs;
 
     /// Checks if we get the same result as `abi.encode`, for some given
@@ -211,4 +932,621 @@ mod tests {
                         6b58b";
         assert_eq!(expected, encoded);
     }
+
+    /// Test that optional values are encoded as a `(bool, T)` tuple.
+    #[test]
+    fn test_abi_encode_option() {
+        let encoded = Some(Uint::from(42u64)).encode().into_inner();
+        let expected = ethabi::encode(&[
+            Token::Bool(true),
+            Token::Uint(U256::from(42u64)),
+        ]);
+        assert_eq!(expected, encoded);
+
+        // both the presence flag and the value slot are zeroed
+        let encoded = Option::<Uint>::None.encode().into_inner();
+        assert_eq!(encoded, vec![0u8; 64]);
+    }
+
+    /// Test that the length prefix holds the length of the ABI payload.
+    #[test]
+    fn test_encode_length_prefixed() {
+        let tokens =
+            [Token::Uint(U256::from(42u64)), Token::String("test".into())];
+        let payload = tokens.encode().into_inner();
+        let encoded = tokens.encode_length_prefixed();
+
+        assert_eq!(encoded.len(), 32 + payload.len());
+        let len = U256::from_big_endian(&encoded[..32]);
+        assert_eq!(len, U256::from(payload.len()));
+        assert_eq!(&encoded[32..], &payload[..]);
+    }
+
+    /// Test that tokens are checked against a Solidity signature.
+    #[test]
+    fn test_validate_against_signature() {
+        let tokens = [
+            Token::Address(ethabi::Address::zero()),
+            Token::Uint(U256::from(42u64)),
+        ];
+        validate_against_signature(&tokens, "transfer(address,uint256)")
+            .expect("Test failed");
+        validate_against_signature(
+            &[Token::Tuple(tokens.to_vec()), Token::Bool(true)],
+            "f((address,uint256),bool)",
+        )
+        .expect("Test failed");
+
+        let result =
+            validate_against_signature(&tokens, "transfer(address,bool)");
+        assert!(matches!(result, Err(EthAbiError::TokenMismatch(_))));
+        let result = validate_against_signature(&tokens, "transfer(address)");
+        assert!(matches!(result, Err(EthAbiError::TokenMismatch(_))));
+        let result = validate_against_signature(&tokens, "transfer");
+        assert!(matches!(result, Err(EthAbiError::InvalidSignature(_))));
+    }
+
+    /// Test that streaming the ABI encoding yields the same bytes as
+    /// encoding it into a buffer.
+    #[test]
+    fn test_encode_streaming() {
+        let valset_update = ValidatorSetArgs {
+            validators: (0..=255u8).map(|i| EthAddress([i; 20])).collect(),
+            voting_powers: (0..=255u64).map(|i| (i * 1000).into()).collect(),
+            epoch: 42.into(),
+        };
+        let mut streamed = vec![];
+        valset_update
+            .encode_to_writer(&mut streamed)
+            .expect("Test failed");
+        assert_eq!(streamed, valset_update.encode().into_inner());
+
+        let tokens = [
+            Token::String("test".into()),
+            Token::Tuple(vec![
+                Token::Bytes(vec![1; 33]),
+                Token::Bool(true),
+                Token::FixedArray(vec![
+                    Token::Address(ethabi::Address::repeat_byte(1)),
+                    Token::Address(ethabi::Address::repeat_byte(2)),
+                ]),
+            ]),
+            Token::FixedBytes(vec![3; 32]),
+            Token::Array(vec![
+                Token::Array(vec![Token::Uint(U256::from(1u64))]),
+                Token::Array(vec![]),
+            ]),
+            Token::Int(U256::MAX),
+        ];
+        let mut streamed = vec![];
+        encode_to_writer(&tokens, &mut streamed).expect("Test failed");
+        assert_eq!(streamed, ethabi::encode(&tokens));
+    }
+
+    /// Checks if we get the same result as `abi.encode(bytes)`, for a
+    /// length that is not a multiple of 32 bytes.
+    #[test]
+    fn test_abi_encode_dyn_bytes() {
+        let expected = "0x0000000000000000000000000000000000000000000000000000000000000020000000000000000000000000000000000000000000000000000000000000002368656c6c6f2c20776f726c642120746869732069732061206c6f6e6720737472696e670000000000000000000000000000000000000000000000000000000000";
+        let expected = HEXLOWER
+            .decode(&expected.as_bytes()[2..])
+            .expect("Test failed");
+        let got =
+            DynBytes::from(b"hello, world! this is a long string".to_vec())
+                .encode()
+                .into_inner();
+        assert_eq!(expected, got);
+    }
+
+    /// Test the topic of the ERC20 `Transfer` event.
+    #[test]
+    fn test_event_topic() {
+        let topic = event_topic("Transfer(address,address,uint256)");
+        assert_eq!(
+            topic.to_string(),
+            "DDF252AD1BE2C89B69C2B068FC378DAA952BA7F163C4A11628F55A4DF523B3EF"
+        );
+    }
+
+    /// Test building the calldata of an ERC20 transfer.
+    #[test]
+    fn test_encode_with_signature() {
+        let signature = "transfer(address,uint256)";
+        assert_eq!(function_selector(signature), [0xa9, 0x05, 0x9c, 0xbb]);
+
+        let args: AbiEncode<2> = [
+            Token::Address(ethabi::Address::repeat_byte(1)),
+            Token::Uint(U256::from(42u64)),
+        ];
+        let calldata = args.encode_with_signature(signature).unwrap();
+        let expected = "a9059cbb\
+                        0000000000000000000000000101010101010101010101010101010101010101\
+                        000000000000000000000000000000000000000000000000000000000000002a";
+        assert_eq!(HEXLOWER.encode(&calldata), expected);
+
+        let result = args.encode_with_signature("transfer(address)");
+        assert!(matches!(result, Err(EthAbiError::TokenMismatch(_))));
+    }
+
+    /// Test that a [`SplitU128`] is reconstructed from its two words.
+    #[test]
+    fn test_split_u128() {
+        let value = 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210_u128;
+        let [high, low] = SplitU128(value).tokenize();
+        let (Token::Uint(high), Token::Uint(low)) = (high, low) else {
+            panic!("Test failed");
+        };
+        assert_eq!(high, U256::from(0x0123_4567_89ab_cdef_u64));
+        assert_eq!(low, U256::from(0xfedc_ba98_7654_3210_u64));
+        assert_eq!((high.as_u128() << 64) | low.as_u128(), value);
+
+        let encoded = SplitU128(value).encode().into_inner();
+        assert_eq!(
+            encoded,
+            ethabi::encode(&[Token::Uint(high), Token::Uint(low)])
+        );
+    }
+
+    /// Test that enums are encoded like `abi.encode(uint8(discriminant))`.
+    #[test]
+    fn test_encode_enum() {
+        enum Status {
+            Pending,
+            Relayed,
+            Refunded,
+            Unknown(usize),
+        }
+
+        impl EncodeEnum for Status {
+            fn discriminant(&self) -> usize {
+                match self {
+                    Self::Pending => 0,
+                    Self::Relayed => 1,
+                    Self::Refunded => 2,
+                    Self::Unknown(discriminant) => *discriminant,
+                }
+            }
+        }
+
+        assert_eq!(
+            Status::Pending.tokenize_enum().unwrap(),
+            Token::Uint(U256::zero())
+        );
+        assert_eq!(
+            Status::Relayed.tokenize_enum().unwrap(),
+            Token::Uint(U256::one())
+        );
+        let token = Status::Refunded.tokenize_enum().unwrap();
+        let expected =
+            "0000000000000000000000000000000000000000000000000000000000000002";
+        assert_eq!(HEXLOWER.encode(&ethabi::encode(&[token])), expected);
+
+        Status::Unknown(255)
+            .tokenize_enum()
+            .expect("The discriminant should fit in a uint8");
+        let result = Status::Unknown(256).tokenize_enum();
+        assert!(matches!(result, Err(EthAbiError::InvalidEnum(_))));
+    }
+
+    /// Test the EIP-712 hashing against the example of the EIP
+    #[test]
+    fn test_eip712_digest() {
+        struct Domain {
+            name: &'static str,
+            version: &'static str,
+            chain_id: u64,
+            verifying_contract: EthAddress,
+        }
+
+        impl Eip712 for Domain {
+            fn encode_type() -> String {
+                "EIP712Domain(string name,string version,uint256 \
+                 chainId,address verifyingContract)"
+                    .to_string()
+            }
+
+            fn encode_data(&self) -> Vec<u8> {
+                let [verifying_contract] = self.verifying_contract.tokenize();
+                ethabi::encode(&[
+                    Token::FixedBytes(keccak_hash(self.name).0.to_vec()),
+                    Token::FixedBytes(keccak_hash(self.version).0.to_vec()),
+                    Token::Uint(self.chain_id.into()),
+                    verifying_contract,
+                ])
+            }
+        }
+
+        struct Person {
+            name: &'static str,
+            wallet: EthAddress,
+        }
+
+        impl Eip712 for Person {
+            fn encode_type() -> String {
+                "Person(string name,address wallet)".to_string()
+            }
+
+            fn encode_data(&self) -> Vec<u8> {
+                let [wallet] = self.wallet.tokenize();
+                ethabi::encode(&[
+                    Token::FixedBytes(keccak_hash(self.name).0.to_vec()),
+                    wallet,
+                ])
+            }
+        }
+
+        struct Mail {
+            from: Person,
+            to: Person,
+            contents: &'static str,
+        }
+
+        impl Eip712 for Mail {
+            fn encode_type() -> String {
+                format!(
+                    "Mail(Person from,Person to,string contents){}",
+                    Person::encode_type()
+                )
+            }
+
+            fn encode_data(&self) -> Vec<u8> {
+                [
+                    self.from.struct_hash(),
+                    self.to.struct_hash(),
+                    keccak_hash(self.contents),
+                ]
+                .into_iter()
+                .flat_map(|KeccakHash(hash)| hash)
+                .collect()
+            }
+        }
+
+        let domain = Domain {
+            name: "Ether Mail",
+            version: "1",
+            chain_id: 1,
+            verifying_contract: EthAddress::from_str(
+                "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC",
+            )
+            .unwrap(),
+        };
+        let mail = Mail {
+            from: Person {
+                name: "Cow",
+                wallet: EthAddress::from_str(
+                    "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826",
+                )
+                .unwrap(),
+            },
+            to: Person {
+                name: "Bob",
+                wallet: EthAddress::from_str(
+                    "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB",
+                )
+                .unwrap(),
+            },
+            contents: "Hello, Bob!",
+        };
+
+        assert_eq!(
+            HEXLOWER.encode(&Mail::type_hash().0),
+            "a0cedeb2dc280ba39b857546d74f5549c3a1d7bdc2dd96bf881f76108e23dac2"
+        );
+        let domain_separator = domain.struct_hash();
+        assert_eq!(
+            HEXLOWER.encode(&domain_separator.0),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+        let struct_hash = mail.struct_hash();
+        assert_eq!(
+            HEXLOWER.encode(&struct_hash.0),
+            "c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e"
+        );
+        assert_eq!(
+            HEXLOWER.encode(&eip712_digest(&domain_separator, &struct_hash).0),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+    }
+
+    /// Test the bridge domain separator against the domain of the EIP-712
+    /// example
+    #[test]
+    fn test_bridge_domain_separator() {
+        let domain_separator =
+            bridge_domain_separator("Ether Mail", "1", 1, [0xcc; 20]);
+        assert_eq!(
+            HEXLOWER.encode(&domain_separator.0),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+    }
+
+    /// Test that fixed-size arrays are encoded like `abi.encode(uint256[3])`
+    #[test]
+    fn test_abi_encode_fixed_array() {
+        let array = [Uint::from(1u64), Uint::from(2u64), Uint::from(3u64)];
+        let encoded = array.encode().into_inner();
+        // the elements are encoded in place, without an offset nor a length
+        let expected = "0000000000000000000000000000000000000000000000000000000000000001\
+                        0000000000000000000000000000000000000000000000000000000000000002\
+                        0000000000000000000000000000000000000000000000000000000000000003";
+        assert_eq!(HEXLOWER.encode(&encoded), expected);
+
+        let [token] = array.tokenize();
+        assert!(
+            matches!(token, Token::FixedArray(elements) if elements.len() == 3)
+        );
+    }
+
+    /// Test that a Merkle proof is encoded like
+    /// `abi.encode(bytes32[] proof, uint256 index)`
+    #[test]
+    fn test_abi_encode_merkle_proof() {
+        let proof =
+            EthMerkleProof::from_siblings([[1u8; 32], [2u8; 32]], 5).unwrap();
+        let encoded = proof.encode().into_inner();
+        // the offset of the array, the index, the array length and the
+        // siblings
+        let mut expected = vec![0; 32];
+        expected[31] = 0x40;
+        expected.extend([0; 31]);
+        expected.push(5);
+        expected.extend([0; 31]);
+        expected.push(2);
+        expected.extend([1; 32]);
+        expected.extend([2; 32]);
+        assert_eq!(encoded, expected);
+
+        let result =
+            EthMerkleProof::from_siblings([vec![1u8; 32], vec![2u8; 31]], 1);
+        assert!(matches!(result, Err(EthAbiError::InvalidMerkleProof(_))));
+    }
+
+    /// Test the two's complement encoding of extreme signed amounts
+    #[test]
+    fn test_encode_signed_saturating() {
+        let encode = |value| {
+            HEXLOWER.encode(&ethabi::encode(&[encode_signed_saturating(value)]))
+        };
+        assert_eq!(encode(0), "00".repeat(32));
+        assert_eq!(encode(1), format!("{}01", "00".repeat(31)));
+        assert_eq!(encode(-1), "ff".repeat(32));
+        assert_eq!(
+            encode(i128::MAX),
+            format!("{}7f{}", "00".repeat(16), "ff".repeat(15))
+        );
+        assert_eq!(
+            encode(i128::MIN),
+            format!("{}80{}", "ff".repeat(16), "00".repeat(15))
+        );
+    }
+
+    /// Test that raw addresses are encoded like `abi.encode(address)`
+    #[test]
+    fn test_abi_encode_raw_address() {
+        let address = [0x11; 20];
+        let [token] = address.tokenize();
+        assert_eq!(token, Token::Address(ethabi::Address::repeat_byte(0x11)));
+        let encoded = address.encode().into_inner();
+        let expected = format!("{}{}", "00".repeat(12), "11".repeat(20));
+        assert_eq!(HEXLOWER.encode(&encoded), expected);
+    }
+
+    /// Test that a client upgrade is encoded like
+    /// `abi.encode(uint256 height, bytes clientState, bytes consensusState)`
+    #[test]
+    fn test_abi_encode_client_upgrade() {
+        let upgrade = ClientUpgradeAbi {
+            height: 42,
+            client_state: vec![0xaa; 33],
+            consensus_state: vec![0xbb; 2],
+        };
+        let encoded = upgrade.encode().into_inner();
+        let word = |value: u8| format!("{}{:02x}", "00".repeat(31), value);
+        // the height and the offsets of the states in the head, then the
+        // length prefixed states padded to 32 bytes in the tail
+        let expected = [
+            word(42),
+            word(0x60),
+            word(0xc0),
+            word(33),
+            format!("{}{}", "aa".repeat(33), "00".repeat(31)),
+            word(2),
+            format!("{}{}", "bb".repeat(2), "00".repeat(30)),
+        ]
+        .concat();
+        assert_eq!(HEXLOWER.encode(&encoded), expected);
+
+        let decoded = ethabi::decode(
+            &[ParamType::Uint(256), ParamType::Bytes, ParamType::Bytes],
+            &encoded,
+        )
+        .expect("Test failed");
+        assert_eq!(decoded, upgrade.tokenize().to_vec());
+    }
+
+    /// Test that a packet commitment is encoded in place, with its timeout
+    /// height as a nested static tuple
+    #[test]
+    fn test_abi_encode_packet_commitment() {
+        let commitment = PacketCommitmentAbi {
+            sequence: 7,
+            timeout_height: (1, 100),
+            timeout_timestamp: 0x1234,
+            data_hash: [0xab; 32],
+        };
+        let encoded = commitment.encode().into_inner();
+        let word = |value: u8| format!("{}{:02x}", "00".repeat(31), value);
+        let expected = [
+            word(7),
+            word(1),
+            word(100),
+            format!("{}{}", "00".repeat(30), "1234"),
+            "ab".repeat(32),
+        ]
+        .concat();
+        assert_eq!(HEXLOWER.encode(&encoded), expected);
+
+        let decoded = ethabi::decode(
+            &[
+                ParamType::Uint(64),
+                ParamType::Tuple(vec![
+                    ParamType::Uint(64),
+                    ParamType::Uint(64),
+                ]),
+                ParamType::Uint(64),
+                ParamType::FixedBytes(32),
+            ],
+            &encoded,
+        )
+        .expect("Test failed");
+        assert_eq!(decoded, commitment.tokenize().to_vec());
+    }
+
+    /// Test the personal-sign hash of a message against the one computed by
+    /// Ethereum clients, e.g. `ethers.utils.hashMessage("hello world")`
+    #[test]
+    fn test_eth_signed_message_hash() {
+        let hash = eth_signed_message_hash(b"hello world");
+        assert_eq!(
+            HEXLOWER.encode(&hash.0),
+            "d9eba16ed0ecae432b71fe008c98cc872bb4cc214d3220a36f365326cf807d68"
+        );
+
+        let message = keccak_hash(b"hello world");
+        assert_eq!(
+            eth_signed_message_hash(&message.0),
+            SignableEthMessage::as_signable(&message)
+        );
+    }
+
+    /// Test the encoding of big-endian integers around the 256-bit boundary
+    #[test]
+    fn test_encode_uint_be() {
+        let token = encode_uint_be(&[0xff; 32]).unwrap();
+        assert_eq!(token, Token::Uint(U256::MAX));
+
+        // leading zeros don't count towards the width
+        let mut bytes = vec![0; 8];
+        bytes.extend([0xff; 32]);
+        let token = encode_uint_be(&bytes).unwrap();
+        assert_eq!(token, Token::Uint(U256::MAX));
+
+        // 2^256 doesn't fit
+        let mut bytes = vec![1];
+        bytes.extend([0; 32]);
+        let result = encode_uint_be(&bytes);
+        assert!(matches!(result, Err(EthAbiError::IntegerOverflow(_))));
+
+        let token = encode_uint_be(&[]).unwrap();
+        assert_eq!(token, Token::Uint(U256::zero()));
+    }
+
+    /// Test that cells differing only by trailing padding are equivalent
+    #[test]
+    fn test_encode_cell_abi_eq() {
+        let param_types = [ParamType::Uint(256), ParamType::String];
+        let cell = AbiEncode::encode(&[
+            Token::Uint(U256::from(42u64)),
+            Token::String("test".into()),
+        ]);
+        let mut padded_data = cell.clone().into_inner();
+        padded_data.extend([0; 32]);
+        let padded = EncodeCell {
+            encoded_data: padded_data,
+            _marker: PhantomData,
+        };
+        assert_ne!(cell, padded);
+        assert!(cell.abi_eq(&padded, &param_types));
+
+        let other = AbiEncode::encode(&[
+            Token::Uint(U256::from(43u64)),
+            Token::String("test".into()),
+        ]);
+        assert!(!cell.abi_eq(&other, &param_types));
+
+        let truncated = EncodeCell {
+            encoded_data: vec![0; 16],
+            _marker: PhantomData,
+        };
+        assert!(!cell.abi_eq(&truncated, &param_types));
+    }
+
+    /// Test that a batch of commitments is encoded like
+    /// `abi.encode(uint256[] heights, bytes32[] commitments)`
+    #[test]
+    fn test_abi_encode_batched_commitments() {
+        let batch = BatchedCommitments::new(
+            vec![7, 8],
+            vec![KeccakHash([1; 32]), KeccakHash([2; 32])],
+        )
+        .unwrap();
+        let encoded = batch.encode().into_inner();
+        let word = |value: u8| format!("{}{:02x}", "00".repeat(31), value);
+        // the offsets of the arrays, then each array with its length
+        let expected = [
+            word(0x40),
+            word(0xa0),
+            word(2),
+            word(7),
+            word(8),
+            word(2),
+            "01".repeat(32),
+            "02".repeat(32),
+        ]
+        .concat();
+        assert_eq!(HEXLOWER.encode(&encoded), expected);
+
+        let result = BatchedCommitments::new(vec![7], vec![]);
+        assert!(matches!(result, Err(EthAbiError::TokenMismatch(_))));
+    }
+
+    /// Test that an encode cell round-trips through its hex string
+    #[test]
+    fn test_encode_cell_hex_roundtrip() {
+        let cell = AbiEncode::encode(&[
+            Token::Uint(U256::from(42u64)),
+            Token::String("test".into()),
+        ]);
+        let hex = cell.to_hex();
+        assert!(hex.starts_with("0x000000"));
+        assert_eq!(hex[2..], HEXLOWER.encode(cell.as_ref()));
+
+        let decoded = EncodeCell::<AbiEncode<2>>::from_hex(&hex).unwrap();
+        assert_eq!(decoded, cell);
+        let decoded =
+            EncodeCell::<AbiEncode<2>>::from_hex(&hex[2..].to_uppercase())
+                .unwrap();
+        assert_eq!(decoded, cell);
+
+        let result = EncodeCell::<AbiEncode<2>>::from_hex("0xzz");
+        assert!(matches!(result, Err(EthAbiError::InvalidHex(_))));
+    }
+
+    /// Test that durations are encoded as whole seconds
+    #[test]
+    fn test_encode_duration() {
+        let timeout = std::time::Duration::from_secs(600);
+        let [token] = timeout.tokenize();
+        assert_eq!(token, Token::Uint(U256::from(600u64)));
+
+        // sub-second precision is truncated
+        let timeout = std::time::Duration::from_millis(600_999);
+        let [token] = timeout.tokenize();
+        assert_eq!(token, Token::Uint(U256::from(600u64)));
+    }
+
+    /// Test the Borsh round trip of cells, which carry no type information
+    #[test]
+    fn test_encode_cell_borsh_roundtrip() {
+        let cell = EncodeCell::new(&[0xab_u8; 20]);
+        let bytes = cell.serialize_to_vec();
+        let decoded = EncodeCell::<[u8; 20]>::try_from_slice(&bytes)
+            .expect("Test failed");
+        assert_eq!(decoded, cell);
+
+        // the same bytes are deserialized as a cell of another type
+        let other = EncodeCell::<DynBytes>::try_from_slice(&bytes)
+            .expect("Test failed");
+        assert_eq!(other.into_inner(), cell.into_inner());
+    }
 }
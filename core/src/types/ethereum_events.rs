@@ -164,6 +164,28 @@ impl From<EthAddress> for H160 {
     }
 }
 
+impl Encode<1> for EthAddress {
+    fn tokenize(&self) -> [Token; 1] {
+        [Token::Address(self.0.into())]
+    }
+}
+
+impl TryFrom<&[u8]> for EthAddress {
+    type Error = eyre::Error;
+
+    /// Build an [`EthAddress`] from a raw byte slice, which must be exactly
+    /// 20 bytes long.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let addr: [u8; 20] = bytes.try_into().map_err(|_| {
+            eyre!(
+                "Ethereum addresses must be 20 bytes long, got {} bytes",
+                bytes.len()
+            )
+        })?;
+        Ok(Self(addr))
+    }
+}
+
 impl Display for EthAddress {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.to_canonical())
@@ -392,6 +414,8 @@ impl From<Erc20Transfer> for TransferToEthereum {
 pub mod tests {
     use std::str::FromStr;
 
+    use data_encoding::HEXLOWER;
+
     use super::*;
 
     #[test]
@@ -422,6 +446,33 @@ pub mod tests {
         assert!(result.is_err());
     }
 
+    /// Test that an [`EthAddress`] is ABI encoded as a left zero-padded
+    /// 32 byte word, like `abi.encode(address)` in Solidity.
+    #[test]
+    fn test_eth_address_abi_encode() {
+        let addr =
+            EthAddress::from_str(testing::DAI_ERC20_ETH_ADDRESS_CHECKSUMMED)
+                .unwrap();
+        let encoded = addr.encode().into_inner();
+        let expected = HEXLOWER
+            .decode(
+                b"0000000000000000000000006b175474e89094c44da98b954eedeac495271d0f",
+            )
+            .unwrap();
+
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn test_eth_address_try_from_bytes() {
+        let addr = EthAddress::try_from(&testing::DAI_ERC20_ETH_ADDRESS.0[..])
+            .unwrap();
+        assert_eq!(addr, testing::DAI_ERC20_ETH_ADDRESS);
+
+        assert!(EthAddress::try_from(&[0u8; 19][..]).is_err());
+        assert!(EthAddress::try_from(&[0u8; 32][..]).is_err());
+    }
+
     /// Test that serde correct serializes EthAddress types to/from lowercase
     /// hex encodings
     #[test]
@@ -163,6 +163,19 @@ impl Encode<1> for KeccakHash {
     }
 }
 
+/// Encodes a slice of hashes as a Solidity `bytes32[]` value. Each
+/// [`KeccakHash`] holds exactly 32 bytes, so every element is a valid
+/// `bytes32`.
+impl Encode<1> for &[KeccakHash] {
+    fn tokenize(&self) -> [Token; 1] {
+        let hashes = self.iter().map(|hash| {
+            let [token] = hash.tokenize();
+            token
+        });
+        [Token::Array(hashes.collect())]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -181,4 +194,24 @@ mod tests {
 
         assert_eq!(deserialized, hash);
     }
+
+    /// Test that a slice of hashes is encoded like `abi.encode(bytes32[])`
+    #[test]
+    fn test_abi_encode_hash_array() {
+        let hashes = [KeccakHash([1; 32]), KeccakHash([2; 32])];
+        let encoded = (&hashes[..]).encode().into_inner();
+        // the offset of the array, its length, and its elements
+        let mut expected = vec![0; 32];
+        expected[31] = 0x20;
+        expected.extend([0; 31]);
+        expected.push(2);
+        expected.extend([1; 32]);
+        expected.extend([2; 32]);
+        assert_eq!(encoded, expected);
+
+        let encoded = (&[][..] as &[KeccakHash]).encode().into_inner();
+        let mut expected = vec![0; 64];
+        expected[31] = 0x20;
+        assert_eq!(encoded, expected);
+    }
 }
@@ -167,6 +167,15 @@ impl From<&PublicKey> for EthAddress {
     }
 }
 
+/// Encodes the key of a validator as the Solidity `address` which Ethereum
+/// derives from it, i.e. the last 20 bytes of the keccak hash of the
+/// uncompressed key without its tag byte.
+impl Encode<1> for PublicKey {
+    fn tokenize(&self) -> [Token; 1] {
+        EthAddress::from(self).tokenize()
+    }
+}
+
 /// Secp256k1 secret key
 #[derive(Debug, Clone)]
 pub struct SecretKey(pub Box<k256::SecretKey>);
@@ -629,6 +638,20 @@ mod test {
         assert_eq!(expected_eth_addr_hex, eth_addr_hex);
     }
 
+    /// Test that a public key is encoded as its Ethereum address
+    #[test]
+    fn test_abi_encode_public_key() {
+        let sk_bytes = HEXLOWER.decode(SECRET_KEY_HEX.as_bytes()).unwrap();
+        let sk = SecretKey::try_from_slice(&sk_bytes[..]).unwrap();
+        let pk: PublicKey = sk.ref_to();
+        let encoded = pk.encode().into_inner();
+        let expected = format!(
+            "{}6ea27154616a29708dce7650b475dd6b82eba6a3",
+            "00".repeat(12)
+        );
+        assert_eq!(HEXLOWER.encode(&encoded), expected);
+    }
+
     /// Test serializing and then de-serializing a signature
     /// with Serde is idempotent.
     #[test]
@@ -21,6 +21,7 @@ use crate::bytes::ByteBuf;
 use crate::hints;
 use crate::ledger::eth_bridge::storage::bridge_pool::BridgePoolProof;
 use crate::types::address::{self, Address};
+use crate::types::eth_abi::{Encode, Token};
 use crate::types::ethereum_events::{GetEventNonce, TransfersToNamada, Uint};
 use crate::types::hash::Hash;
 use crate::types::keccak::{KeccakHash, TryFromError};
@@ -227,6 +228,14 @@ impl From<BlockHeight> for u64 {
     }
 }
 
+/// Encodes a block height as a Solidity `uint64`, which is padded to a full
+/// 32-byte slot like any other integer.
+impl Encode<1> for BlockHeight {
+    fn tokenize(&self) -> [Token; 1] {
+        [Token::Uint(self.0.into())]
+    }
+}
+
 impl FromStr for BlockHeight {
     type Err = ParseIntError;
 
@@ -1108,6 +1117,14 @@ impl From<Epoch> for u64 {
     }
 }
 
+/// Encodes an epoch as a Solidity `uint256`. An epoch is within the range
+/// of a `uint64`, but it takes a full 32-byte slot like any other integer.
+impl Encode<1> for Epoch {
+    fn tokenize(&self) -> [Token; 1] {
+        [Token::Uint(self.0.into())]
+    }
+}
+
 // TODO remove this once it's not being used
 impl From<Epoch> for usize {
     fn from(epoch: Epoch) -> Self {
@@ -1476,6 +1493,24 @@ pub mod tests {
         }
     }
 
+    /// Test that an epoch is encoded like `abi.encode(uint256)`
+    #[test]
+    fn test_abi_encode_epoch() {
+        let encoded = Epoch(0x1234).encode().into_inner();
+        let mut expected = vec![0; 30];
+        expected.extend([0x12, 0x34]);
+        assert_eq!(encoded, expected);
+    }
+
+    /// Test that a block height is encoded like `abi.encode(uint64)`
+    #[test]
+    fn test_abi_encode_block_height() {
+        let encoded = BlockHeight(0x0102_0304_0506_0708).encode().into_inner();
+        let mut expected = vec![0; 24];
+        expected.extend([0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        assert_eq!(encoded, expected);
+    }
+
     /// Test that providing an [`EthEventsQueue`] with an event containing
     /// a nonce identical to the next expected nonce in Namada yields the
     /// event itself.
@@ -12,6 +12,7 @@ use num_traits::CheckedMul;
 use uint::construct_uint;
 
 use super::dec::{Dec, POS_DECIMAL_PRECISION};
+use crate::types::eth_abi::{Encode, Token};
 use crate::types::token;
 use crate::types::token::{Amount, AmountParseError, MaspDenom};
 
@@ -306,6 +307,14 @@ construct_uint! {
     pub struct Uint(4);
 }
 
+/// Encodes the integer as a Solidity `uint256`, which always holds its 256
+/// bits.
+impl Encode<1> for Uint {
+    fn tokenize(&self) -> [Token; 1] {
+        [Token::Uint(ethabi::Uint(self.0))]
+    }
+}
+
 impl serde::Serialize for Uint {
     fn serialize<S>(
         &self,
@@ -1057,4 +1066,15 @@ mod test_uint {
         assert_eq!(e.checked_mul_div(c, b), Some((Uint::zero(), c)));
         assert_eq!(d.checked_mul_div(a, e), None);
     }
+
+    /// Test that the maximum integer is encoded like `abi.encode(uint256)`
+    #[test]
+    fn test_abi_encode_max() {
+        let encoded = Uint::MAX.encode().into_inner();
+        assert_eq!(encoded, vec![0xff; 32]);
+        let encoded = Uint::from(0x1234u64).encode().into_inner();
+        let mut expected = vec![0; 30];
+        expected.extend([0x12, 0x34]);
+        assert_eq!(encoded, expected);
+    }
 }
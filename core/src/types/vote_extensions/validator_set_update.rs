@@ -267,8 +267,9 @@ impl VotingPowersMapExt for VotingPowersMap {
 
 /// Convert an [`Epoch`] to a [`Token`].
 #[inline]
-fn epoch_to_token(Epoch(e): Epoch) -> Token {
-    Token::Uint(e.into())
+fn epoch_to_token(epoch: Epoch) -> Token {
+    let [token] = epoch.tokenize();
+    token
 }
 
 /// Compute the keccak hash of a validator set update.
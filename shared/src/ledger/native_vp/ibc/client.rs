@@ -0,0 +1,6732 @@
+//! IBC client validation for the IBC native VP
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use borsh::BorshDeserialize;
+use borsh_ext::BorshSerializeExt;
+use ics23::commitment_proof::Proof as Ics23Proof;
+use namada_core::ledger::ibc::context::client::{
+    AnyClientState, AnyConsensusState,
+};
+use prost::Message;
+
+use super::{Error, Ibc, StateChange, VpResult};
+use crate::ibc::clients::tendermint::types::{
+    ConsensusState as TmConsensusStateType, Header as TmHeader,
+};
+use crate::ibc::core::client::context::client_state::ClientStateCommon;
+use crate::ibc::core::client::context::consensus_state::ConsensusState;
+use crate::ibc::core::client::types::msgs::{
+    ClientMsg, MsgCreateClient, MsgUpdateClient, MsgUpgradeClient,
+};
+use crate::ibc::core::client::types::Height;
+use crate::ibc::core::commitment_types::commitment::{
+    CommitmentPrefix, CommitmentProofBytes, CommitmentRoot,
+};
+use crate::ibc::core::commitment_types::merkle::MerkleProof;
+use crate::ibc::core::commitment_types::proto::v1::MerkleProof as RawMerkleProof;
+use crate::ibc::core::handler::types::msgs::MsgEnvelope;
+use crate::ibc::core::host::types::identifiers::{
+    ChainId, ClientId, ClientType,
+};
+use crate::ibc::core::host::types::path::Path;
+use crate::ibc::primitives::proto::Any;
+use crate::ibc::primitives::Timestamp;
+use crate::ledger::ibc::storage::{
+    client_counter_key, client_id, client_state_key, client_update_height_key,
+    client_update_timestamp_key, consensus_height, consensus_state_key,
+    consensus_state_prefix, ibc_key, is_ibc_key,
+};
+use crate::ledger::native_vp::VpEnv;
+use crate::ledger::storage::{self as ledger_storage, StorageHasher};
+use crate::ledger::storage_api::{self, StorageRead};
+use crate::tendermint::{Hash as TmHash, Time as TmTime};
+use crate::types::storage::Key;
+use crate::vm::WasmCacheAccess;
+
+/// The key segment under which all the client data are stored
+const CLIENTS_PREFIX: &str = "clients";
+
+/// The type URLs of the client messages validated by the client checks
+const CLIENT_MSG_TYPE_URLS: [&str; 3] = [
+    "/ibc.core.client.v1.MsgCreateClient",
+    "/ibc.core.client.v1.MsgUpdateClient",
+    "/ibc.core.client.v1.MsgUpgradeClient",
+];
+
+/// The commitment root the IBC handler stores in the consensus state of an
+/// upgraded Tendermint client, since the root of the new chain isn't known
+const SENTINEL_ROOT: &[u8] = b"sentinel_root";
+
+/// The type URLs of the client states an upgraded client can have. The
+/// version of the client state is declared in its type URL.
+const SUPPORTED_CLIENT_STATE_TYPE_URLS: [&str; 2] = [
+    "/ibc.lightclients.tendermint.v1.ClientState",
+    "/ibc.mock.ClientState",
+];
+
+/// The policy for the prior consensus states of an upgraded client
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConsensusHistoryPolicy {
+    /// The prior consensus states have to be kept. The handler keeps them
+    /// when storing the upgraded states.
+    #[default]
+    Retain,
+    /// The prior consensus states have to be removed, except the one at the
+    /// height of the upgraded client
+    Prune,
+}
+
+/// The configuration of the optional client checks. The default is the
+/// configuration the ledger validates the clients with.
+#[derive(Debug, Clone)]
+pub struct ClientValidationConfig {
+    /// Whether the update verification re-derives the consensus state from
+    /// the header to check the stored one, i.e. that the root of the stored
+    /// consensus state is anchored to the app hash of the header. It's
+    /// disabled by default because it's expensive and the IBC handler has
+    /// already derived the stored consensus state.
+    pub deep_verification: bool,
+    /// Whether the upgrade verification also requires the stored states to
+    /// keep the fields which can't change during an upgrade, i.e. the fields
+    /// chosen by the relayer in the prior client state, and the sentinel
+    /// root stored by the handler. It's disabled by default because the
+    /// fields chosen by the counterparty chain are enough to check the
+    /// upgrade.
+    pub strict_upgrade_equality: bool,
+    /// The chain ID a created client has to track. No chain ID is expected
+    /// by default, since the counterparty is chosen by the relayer.
+    pub expected_counterparty_chain_id: Option<ChainId>,
+    /// What the client upgrade has to do with the prior consensus states
+    pub upgrade_consensus_history: ConsensusHistoryPolicy,
+    /// The maximum size of the transaction data, checked before decoding it
+    /// to bound the allocations of the decoding. It's 1 MiB by default.
+    pub max_client_tx_data_size: usize,
+    /// The maximum size of each proof in an upgrade message, to bound the
+    /// cost of decoding and verifying the proofs. It's 64 KiB by default.
+    pub max_upgrade_proof_size: usize,
+}
+
+impl Default for ClientValidationConfig {
+    fn default() -> Self {
+        Self {
+            deep_verification: false,
+            strict_upgrade_equality: false,
+            expected_counterparty_chain_id: None,
+            upgrade_consensus_history: ConsensusHistoryPolicy::default(),
+            max_client_tx_data_size: 1 << 20,
+            max_upgrade_proof_size: 64 * 1024,
+        }
+    }
+}
+
+/// The client message in the transaction data, decoded before dispatching
+/// the client checks
+#[derive(Debug, Clone)]
+pub enum ClientTxPayload {
+    /// A message creating a client
+    Create(MsgCreateClient),
+    /// A message updating a client
+    Update(MsgUpdateClient),
+    /// A message upgrading a client
+    Upgrade(MsgUpgradeClient),
+    /// Any other transaction data, e.g. a misbehaviour or a non-client
+    /// message, which isn't validated by the client checks
+    Other,
+}
+
+impl ClientTxPayload {
+    /// Decode the transaction data. The data which isn't an `Any` message
+    /// of a client message type is [`ClientTxPayload::Other`], whereas a
+    /// client message which can't be decoded is an error. The data larger
+    /// than the given size is rejected before decoding anything.
+    pub fn decode(tx_data: &[u8], max_size: usize) -> VpResult<Self> {
+        if tx_data.len() > max_size {
+            return Err(Error::Decoding(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "The transaction data is too large: Size {}, Max size \
+                     {max_size}",
+                    tx_data.len()
+                ),
+            )));
+        }
+        let any = match Any::decode(tx_data) {
+            Ok(any)
+                if CLIENT_MSG_TYPE_URLS.contains(&any.type_url.as_str()) =>
+            {
+                any
+            }
+            _ => return Ok(Self::Other),
+        };
+        let type_url = any.type_url.clone();
+        let msg = MsgEnvelope::try_from(any).map_err(|e| {
+            Error::Decoding(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Decoding the client message failed: Type URL {type_url}, \
+                     Error {e}"
+                ),
+            ))
+        })?;
+        Ok(match msg {
+            MsgEnvelope::Client(ClientMsg::CreateClient(msg)) => {
+                Self::Create(msg)
+            }
+            MsgEnvelope::Client(ClientMsg::UpdateClient(msg)) => {
+                Self::Update(msg)
+            }
+            MsgEnvelope::Client(ClientMsg::UpgradeClient(msg)) => {
+                Self::Upgrade(msg)
+            }
+            _ => Self::Other,
+        })
+    }
+}
+
+/// Read access to the IBC client data
+pub trait ClientReader {
+    /// Get the client state
+    fn client_state(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<Option<AnyClientState>>;
+
+    /// Get the consensus state at the given height
+    fn consensus_state(
+        &self,
+        client_id: &ClientId,
+        height: Height,
+    ) -> VpResult<Option<AnyConsensusState>>;
+
+    /// Get the client counter
+    fn client_counter(&self) -> VpResult<u64>;
+
+    /// Get the client type
+    fn client_type(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<Option<ClientType>> {
+        Ok(self
+            .client_state(client_id)?
+            .map(|client_state| client_state.client_type()))
+    }
+}
+
+/// Hooks invoked at the outcomes of the client validation, e.g. to count
+/// how often each validation branch fails. All the hooks are no-op by
+/// default.
+pub trait ValidationObserver {
+    /// Called when the client created by the message has been validated
+    fn on_created(&self, _msg: &MsgCreateClient) {}
+
+    /// Called when the client updated by the message has been validated
+    fn on_updated(&self, _msg: &MsgUpdateClient) {}
+
+    /// Called when the client upgraded by the message has been validated
+    fn on_upgraded(&self, _msg: &MsgUpgradeClient) {}
+
+    /// Called when the validation fails
+    fn on_error(&self, _error: &Error) {}
+}
+
+/// The observer ignoring all the validation outcomes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopObserver;
+
+impl ValidationObserver for NoopObserver {}
+
+/// The complete view of a client, e.g. for an RPC snapshot. The missing
+/// pieces are `None`.
+#[derive(Debug, Clone)]
+pub struct ClientView {
+    /// The type of the client
+    pub client_type: Option<ClientType>,
+    /// The client state
+    pub client_state: Option<AnyClientState>,
+    /// The client counter, shared by all the clients
+    pub client_counter: Option<u64>,
+    /// The heights of the consensus states of the client, in ascending
+    /// order
+    pub consensus_heights: Vec<Height>,
+}
+
+/// The action of a transaction on a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientAction {
+    /// The client is created
+    Created,
+    /// The client is updated, e.g. with a header or a misbehaviour
+    Updated,
+    /// The client is upgraded
+    Upgraded,
+    /// The client state isn't changed
+    Unchanged,
+}
+
+impl<'a, DB, H, CA> ClientReader for Ibc<'a, DB, H, CA>
+where
+    DB: 'static + ledger_storage::DB + for<'iter> ledger_storage::DBIter<'iter>,
+    H: 'static + StorageHasher,
+    CA: 'static + WasmCacheAccess,
+{
+    fn client_state(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<Option<AnyClientState>> {
+        self.client_state_post(client_id)
+    }
+
+    fn consensus_state(
+        &self,
+        client_id: &ClientId,
+        height: Height,
+    ) -> VpResult<Option<AnyConsensusState>> {
+        self.consensus_state_post(client_id, height)
+    }
+
+    fn client_counter(&self) -> VpResult<u64> {
+        Ibc::client_counter(self)
+    }
+}
+
+impl<'a, DB, H, CA> Ibc<'a, DB, H, CA>
+where
+    DB: 'static + ledger_storage::DB + for<'iter> ledger_storage::DBIter<'iter>,
+    H: 'static + StorageHasher,
+    CA: 'static + WasmCacheAccess,
+{
+    /// Get the client counter in the posterior state
+    pub fn client_counter(&self) -> VpResult<u64> {
+        let bytes = self
+            .ctx
+            .read_bytes_post(&client_counter_key())
+            .map_err(Error::NativeVpError)?;
+        decode_client_counter(bytes)
+    }
+
+    /// Get the client counter in the prior state
+    pub fn client_counter_pre(&self) -> VpResult<u64> {
+        let bytes = self
+            .ctx
+            .read_bytes_pre(&client_counter_key())
+            .map_err(Error::NativeVpError)?;
+        decode_client_counter(bytes)
+    }
+
+    /// Get the complete view of the client in the posterior state
+    pub fn client_view(&self, client_id: &ClientId) -> VpResult<ClientView> {
+        let client_counter = self
+            .ctx
+            .read_bytes_post(&client_counter_key())
+            .map_err(Error::NativeVpError)?
+            .map(|bytes| decode_client_counter(Some(bytes)))
+            .transpose()?;
+        let consensus_heights = self.consensus_state_heights(client_id)?;
+        Ok(ClientView {
+            client_type: ClientReader::client_type(self, client_id)?,
+            client_state: self.client_state_post(client_id)?,
+            client_counter,
+            consensus_heights,
+        })
+    }
+
+    /// Classify the action of the transaction on the client, from the change
+    /// of its client state and the client message in the transaction data.
+    /// A client state can't be deleted.
+    pub fn client_action(
+        &self,
+        client_id: &ClientId,
+        tx_data: &[u8],
+    ) -> VpResult<ClientAction> {
+        let action = match self.classify_change(&client_state_key(client_id))? {
+            StateChange::Created => ClientAction::Created,
+            StateChange::Updated => match ClientTxPayload::decode(
+                tx_data,
+                ClientValidationConfig::default().max_client_tx_data_size,
+            )? {
+                ClientTxPayload::Upgrade(msg)
+                    if msg.client_id == *client_id =>
+                {
+                    ClientAction::Upgraded
+                }
+                _ => ClientAction::Updated,
+            },
+            StateChange::NotChanged => ClientAction::Unchanged,
+            StateChange::Deleted => {
+                return Err(Error::StateChange(format!(
+                    "The client state is deleted: ID {client_id}"
+                )));
+            }
+        };
+        Ok(action)
+    }
+
+    /// Get the client ID which the next client creation will assign to a
+    /// client of the given type, i.e. `{client_type}-{counter}` with the
+    /// client counter in the posterior state
+    pub fn next_client_id(
+        &self,
+        client_type: ClientType,
+    ) -> VpResult<ClientId> {
+        new_client_id(client_type, self.client_counter()?)
+    }
+
+    /// Get the IDs of all the clients in the posterior state
+    pub fn iter_client_ids(&self) -> VpResult<Vec<ClientId>> {
+        let prefix = ibc_key(CLIENTS_PREFIX).map_err(|e| Error::KeyError {
+            key: CLIENTS_PREFIX.to_string(),
+            reason: format!("Creating the client key prefix failed: {e}"),
+        })?;
+        let post = self.ctx.post();
+        let iter = storage_api::iter_prefix_bytes(&post, &prefix)
+            .map_err(Error::NativeVpError)?;
+        let mut client_ids = Vec::new();
+        for result in iter {
+            let (key, _) = result.map_err(Error::NativeVpError)?;
+            // Only the client state key identifies a client, the other keys
+            // (the counter, consensus states, etc.) are skipped
+            if let Ok(client_id) = client_id(&key) {
+                if key == client_state_key(&client_id) {
+                    client_ids.push(client_id);
+                }
+            }
+        }
+        Ok(client_ids)
+    }
+
+    /// Get the IDs of the frozen clients in the posterior state
+    pub fn frozen_client_ids(&self) -> VpResult<Vec<ClientId>> {
+        let mut frozen = Vec::new();
+        for client_id in self.iter_client_ids()? {
+            if self.is_client_frozen(&client_id)? {
+                frozen.push(client_id);
+            }
+        }
+        Ok(frozen)
+    }
+
+    /// Check if the client is frozen in the posterior state
+    pub fn is_client_frozen(&self, client_id: &ClientId) -> VpResult<bool> {
+        let client_state =
+            self.client_state_post(client_id)?.ok_or_else(|| {
+                Error::ClientError(format!(
+                    "The client state doesn't exist: ID {client_id}"
+                ))
+            })?;
+        Ok(client_state.is_frozen())
+    }
+
+    /// Get the trusting period of the client in the posterior state. Returns
+    /// `None` if the client doesn't exist or isn't a Tendermint client.
+    pub fn trusting_period(&self, client_id: &ClientId) -> Option<Duration> {
+        match self.client_state_post(client_id).ok()?? {
+            AnyClientState::Tendermint(cs) => Some(cs.inner().trusting_period),
+            // The mock client state for testing
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// Check that the timestamps of the consensus states of the client in the
+    /// posterior state strictly increase with their heights
+    pub fn validate_timestamp_monotonicity(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<()> {
+        let consensus_states = self.consensus_state_timestamps(client_id)?;
+        for pair in consensus_states.windows(2) {
+            let (prev_height, prev_timestamp) = &pair[0];
+            let (height, timestamp) = &pair[1];
+            if timestamp.nanoseconds() <= prev_timestamp.nanoseconds() {
+                return Err(Error::ClientError(format!(
+                    "The consensus state timestamps don't increase: ID \
+                     {client_id}, Height {prev_height} at {prev_timestamp}, \
+                     Height {height} at {timestamp}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that all the consensus states of the client written by the
+    /// transaction are stored under the given revision number of the client.
+    /// The consensus states existing in the prior state were checked when
+    /// they were written, so only the keys changed by the transaction are
+    /// read.
+    pub fn validate_consensus_state_revisions(
+        &self,
+        client_id: &ClientId,
+        revision_number: u64,
+    ) -> VpResult<()> {
+        let heights = self.consensus_heights_written(client_id)?;
+        check_consensus_state_revisions(client_id, revision_number, heights)
+    }
+
+    /// Check that the consensus states of the client written by the
+    /// transaction don't have validator hashes contradicting the consensus
+    /// states at the adjacent heights in the posterior state.
+    ///
+    /// The validator set is derived from the application state, so it can't
+    /// change between two adjacent blocks with the same commitment root. The
+    /// linkage to the validator set of the next header can't be verified
+    /// without the headers, so this is only a sanity check. Only the written
+    /// consensus states and their neighbors are read.
+    pub fn validate_validator_hash_linkage(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<()> {
+        let heights = self.consensus_heights_around_written(client_id)?;
+        check_validator_hash_linkage(
+            client_id,
+            &self.consensus_states_at_heights(client_id, heights)?,
+        )
+    }
+
+    /// Check that the transaction doesn't overwrite any consensus state of
+    /// the client existing in the prior state with a different one. Deleting
+    /// a consensus state, e.g. pruning an expired one, is allowed.
+    pub fn validate_consensus_states_not_overwritten(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<()> {
+        let prefix = consensus_state_prefix(client_id);
+        for key in self.ctx.keys_changed {
+            if key.split_prefix(&prefix).is_none() {
+                continue;
+            }
+            let prior =
+                self.ctx.read_bytes_pre(key).map_err(Error::NativeVpError)?;
+            let posterior = self
+                .ctx
+                .read_bytes_post(key)
+                .map_err(Error::NativeVpError)?;
+            if let (Some(prior), Some(posterior)) = (prior, posterior) {
+                if prior != posterior {
+                    let height = parse_consensus_height(key)?;
+                    return Err(Error::ClientError(format!(
+                        "The consensus state was overwritten: ID {client_id}, \
+                         Height {height}"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the consensus states of the client which don't exist in the prior
+    /// state and are written by the transaction
+    pub fn consensus_states_added(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<Vec<(Height, AnyConsensusState)>> {
+        let prefix = consensus_state_prefix(client_id);
+        let mut added = Vec::new();
+        for key in self.ctx.keys_changed {
+            if key.split_prefix(&prefix).is_none()
+                || self.ctx.has_key_pre(key).map_err(Error::NativeVpError)?
+            {
+                continue;
+            }
+            if let Some(bytes) = self
+                .ctx
+                .read_bytes_post(key)
+                .map_err(Error::NativeVpError)?
+            {
+                let height = parse_consensus_height(key)?;
+                added.push((height, decode_consensus_state(key, bytes)?));
+            }
+        }
+        Ok(added)
+    }
+
+    /// Get the heights of the consensus states of the client which don't
+    /// exist in the prior state and are written by the transaction, without
+    /// decoding the consensus states
+    fn consensus_heights_added(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<Vec<Height>> {
+        let prefix = consensus_state_prefix(client_id);
+        let mut added = Vec::new();
+        for key in self.ctx.keys_changed {
+            if key.split_prefix(&prefix).is_none()
+                || self.ctx.has_key_pre(key).map_err(Error::NativeVpError)?
+                || !self.ctx.has_key_post(key).map_err(Error::NativeVpError)?
+            {
+                continue;
+            }
+            added.push(parse_consensus_height(key)?);
+        }
+        Ok(added)
+    }
+
+    /// Get the heights of the consensus states of the client written by the
+    /// transaction and existing in the posterior state, without decoding the
+    /// consensus states
+    fn consensus_heights_written(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<Vec<Height>> {
+        let prefix = consensus_state_prefix(client_id);
+        let mut written = Vec::new();
+        for key in self.ctx.keys_changed {
+            if key.split_prefix(&prefix).is_none()
+                || !self.ctx.has_key_post(key).map_err(Error::NativeVpError)?
+            {
+                continue;
+            }
+            written.push(parse_consensus_height(key)?);
+        }
+        Ok(written)
+    }
+
+    /// Check that the consensus state at the latest height of the client has
+    /// the newest timestamp of all the consensus states in the posterior
+    /// state
+    pub fn validate_latest_consensus_state_newest(
+        &self,
+        client_id: &ClientId,
+        latest: &AnyConsensusState,
+    ) -> VpResult<()> {
+        check_latest_consensus_state_newest(
+            client_id,
+            latest,
+            &self.consensus_states_post(client_id)?,
+        )
+    }
+
+    /// Get all the consensus states of the client in the posterior state,
+    /// decoded in one pass over the storage and ordered by their heights
+    pub fn consensus_states_post(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<Vec<(Height, AnyConsensusState)>> {
+        let prefix = consensus_state_prefix(client_id);
+        let post = self.ctx.post();
+        let iter = storage_api::iter_prefix_bytes(&post, &prefix)
+            .map_err(Error::NativeVpError)?;
+        let mut consensus_states = Vec::new();
+        for result in iter {
+            let (key, bytes) = result.map_err(Error::NativeVpError)?;
+            let height = parse_consensus_height(&key)?;
+            consensus_states
+                .push((height, decode_consensus_state(&key, bytes)?));
+        }
+        // The keys are ordered by their string, e.g. `0-10` before `0-9`
+        consensus_states.sort_by_key(|(height, _)| *height);
+        Ok(consensus_states)
+    }
+
+    /// Get the heights of the consensus states written by the transaction and
+    /// the heights adjacent to them
+    fn consensus_heights_around_written(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<Vec<Height>> {
+        Ok(self
+            .consensus_heights_written(client_id)?
+            .into_iter()
+            .flat_map(|height| {
+                [
+                    height.decrement().ok(),
+                    Some(height),
+                    Some(height.increment()),
+                ]
+            })
+            .flatten()
+            .collect())
+    }
+
+    /// Get the consensus states of the client existing at the given heights
+    /// in the posterior state, ordered by their heights without duplicates
+    fn consensus_states_at_heights(
+        &self,
+        client_id: &ClientId,
+        heights: impl IntoIterator<Item = Height>,
+    ) -> VpResult<Vec<(Height, AnyConsensusState)>> {
+        let mut heights: Vec<_> = heights.into_iter().collect();
+        heights.sort();
+        heights.dedup();
+        let mut consensus_states = Vec::new();
+        for height in heights {
+            if let Some(consensus_state) =
+                self.consensus_state_post(client_id, height)?
+            {
+                consensus_states.push((height, consensus_state));
+            }
+        }
+        Ok(consensus_states)
+    }
+
+    /// Get the timestamps of all the consensus states of the client in the
+    /// posterior state, ordered by their heights
+    fn consensus_state_timestamps(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<Vec<(Height, Timestamp)>> {
+        Ok(self
+            .consensus_states_post(client_id)?
+            .into_iter()
+            .map(|(height, consensus_state)| {
+                (height, consensus_state.timestamp())
+            })
+            .collect())
+    }
+
+    /// Get the heights of all the consensus states of the client in the
+    /// posterior state, in ascending order. The heights are parsed from the
+    /// keys without decoding the consensus states.
+    pub fn consensus_state_heights(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<Vec<Height>> {
+        let prefix = consensus_state_prefix(client_id);
+        let post = self.ctx.post();
+        let iter = storage_api::iter_prefix_bytes(&post, &prefix)
+            .map_err(Error::NativeVpError)?;
+        let mut heights = Vec::new();
+        for result in iter {
+            let (key, _) = result.map_err(Error::NativeVpError)?;
+            heights.push(parse_consensus_height(&key)?);
+        }
+        // The keys are ordered by their string, e.g. `0-10` before `0-9`
+        heights.sort();
+        Ok(heights)
+    }
+
+    /// Check that all the IBC keys actually changed by the transaction are
+    /// under the prefix of the client. The keys of the other modules, e.g.
+    /// the fees paid by the transaction, aren't checked.
+    pub fn assert_only_client_keys_changed(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<()> {
+        let path = format!("{CLIENTS_PREFIX}/{client_id}");
+        let prefix = ibc_key(&path).map_err(|e| Error::KeyError {
+            key: path.clone(),
+            reason: format!("Creating the client key prefix failed: {e}"),
+        })?;
+        for key in self.ctx.keys_changed {
+            if !is_ibc_key(key) || key.split_prefix(&prefix).is_some() {
+                continue;
+            }
+            let pre =
+                self.ctx.read_bytes_pre(key).map_err(Error::NativeVpError)?;
+            let post = self
+                .ctx
+                .read_bytes_post(key)
+                .map_err(Error::NativeVpError)?;
+            if pre != post {
+                return Err(Error::StateChange(format!(
+                    "An IBC key outside of the client was changed: ID \
+                     {client_id}, Key {key}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that all the client keys changed by the creation, e.g. the client
+    /// state and the consensus state keys, are for the created client. The
+    /// client type is stored in the client state, so there's no separate key.
+    fn validate_created_client_keys(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<()> {
+        let prefix = ibc_key(CLIENTS_PREFIX).map_err(|e| Error::KeyError {
+            key: CLIENTS_PREFIX.to_string(),
+            reason: format!("Creating the client key prefix failed: {e}"),
+        })?;
+        let counter_key = client_counter_key();
+        for key in self.ctx.keys_changed {
+            if key.split_prefix(&prefix).is_none() || *key == counter_key {
+                continue;
+            }
+            // The parameter shadows the key parser `client_id`
+            let key_client_id = crate::ledger::ibc::storage::client_id(key)
+                .map_err(|e| Error::KeyError {
+                    key: key.to_string(),
+                    reason: format!("Parsing the client ID failed: {e}"),
+                })?;
+            if key_client_id != *client_id {
+                return Err(Error::KeyError {
+                    key: key.to_string(),
+                    reason: format!(
+                        "The key is for another client than the created one: \
+                         ID {client_id}, Key ID {key_client_id}"
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the client counter is at least the number of the existing
+    /// clients
+    pub fn validate_counter_consistency(&self) -> VpResult<()> {
+        let counter = self.client_counter()?;
+        let num_clients = self.iter_client_ids()?.len() as u64;
+        if counter < num_clients {
+            return Err(Error::ClientError(format!(
+                "The client counter is less than the number of the clients: \
+                 Counter {counter}, Clients {num_clients}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check that the client counter isn't changed, for the transactions
+    /// which don't create a client
+    fn validate_counter_unchanged(&self) -> VpResult<()> {
+        let prior = self.client_counter_pre()?;
+        let posterior = self.client_counter()?;
+        if prior != posterior {
+            return Err(Error::ClientError(format!(
+                "The client counter was changed: Prior {prior}, Posterior \
+                 {posterior}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check that the updated client isn't created by the same transaction.
+    /// Such a client has no prior state to validate the update against, so
+    /// its prior and posterior states would be ambiguous.
+    fn validate_not_created_in_tx(&self, client_id: &ClientId) -> VpResult<()> {
+        // Only the existence matters, so that the client states are decoded
+        // once by the update
+        let key = client_state_key(client_id);
+        let is_created =
+            !self.ctx.has_key_pre(&key).map_err(Error::NativeVpError)?
+                && self.ctx.has_key_post(&key).map_err(Error::NativeVpError)?
+                && self.client_counter_pre()? != self.client_counter()?;
+        if is_created {
+            return Err(Error::StateChange(format!(
+                "The client was created and updated in the same transaction: \
+                 ID {client_id}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Validate the client changes according to the client message in the
+    /// transaction. Messages other than client messages are ignored.
+    ///
+    /// The IBC VP runs this validation only with the `ibc-client-validation`
+    /// feature.
+    pub fn validate_client(&self, tx_data: &[u8]) -> VpResult<()> {
+        self.validate_client_with_observer(tx_data, &NoopObserver)
+    }
+
+    /// Validate the client like `validate_client`, notifying the observer of
+    /// the outcome
+    pub fn validate_client_with_observer(
+        &self,
+        tx_data: &[u8],
+        observer: &dyn ValidationObserver,
+    ) -> VpResult<()> {
+        self.validate_client_with_config(
+            tx_data,
+            &ClientValidationConfig::default(),
+            observer,
+        )
+    }
+
+    /// Validate the client like `validate_client`, with the optional checks
+    /// enabled by the given configuration, notifying the observer of the
+    /// outcome
+    pub fn validate_client_with_config(
+        &self,
+        tx_data: &[u8],
+        config: &ClientValidationConfig,
+        observer: &dyn ValidationObserver,
+    ) -> VpResult<()> {
+        let payload =
+            ClientTxPayload::decode(tx_data, config.max_client_tx_data_size);
+        let result = payload.and_then(|payload| {
+            self.run_client_checks(
+                &payload,
+                config,
+                &mut ClientChecks::fail_fast(),
+            )?;
+            match &payload {
+                ClientTxPayload::Create(msg) => observer.on_created(msg),
+                ClientTxPayload::Update(msg) => observer.on_updated(msg),
+                ClientTxPayload::Upgrade(msg) => observer.on_upgraded(msg),
+                ClientTxPayload::Other => {}
+            }
+            Ok(())
+        });
+        if let Err(e) = &result {
+            observer.on_error(e);
+        }
+        result
+    }
+
+    /// Validate the client like `validate_client_with_config`, but run all
+    /// the independent checks instead of failing on the first error, for
+    /// diagnostics. Returns whether the client is valid with all the errors.
+    pub fn validate_client_verbose(
+        &self,
+        tx_data: &[u8],
+        config: &ClientValidationConfig,
+    ) -> (bool, Vec<Error>) {
+        let mut checks = ClientChecks::collecting();
+        let payload =
+            ClientTxPayload::decode(tx_data, config.max_client_tx_data_size);
+        let result = payload.and_then(|payload| {
+            self.run_client_checks(&payload, config, &mut checks)
+        });
+        let errors = checks.into_errors(result);
+        (errors.is_empty(), errors)
+    }
+
+    /// Run the checks of the client message in the transaction. The same
+    /// checks are run by the fail-fast and the verbose validation.
+    fn run_client_checks(
+        &self,
+        payload: &ClientTxPayload,
+        config: &ClientValidationConfig,
+        checks: &mut ClientChecks,
+    ) -> VpResult<()> {
+        match payload {
+            ClientTxPayload::Create(msg) => self.verify_create_client(
+                msg,
+                config.expected_counterparty_chain_id.as_ref(),
+                checks,
+            ),
+            ClientTxPayload::Update(msg) => {
+                self.verify_update_client(msg, config.deep_verification, checks)
+            }
+            ClientTxPayload::Upgrade(msg) => {
+                self.verify_upgrade_client(msg, config, checks)
+            }
+            ClientTxPayload::Other => Ok(()),
+        }
+    }
+
+    /// Verify that the client to be created by the create message doesn't
+    /// exist yet. When a transaction creating a client is processed twice,
+    /// the second attempt is rejected instead of being validated again.
+    ///
+    /// The consensus state stored for the latest height of the client also
+    /// has to be the one in the message, and all the consensus states written
+    /// at the creation have to be of the client type. It has to be the only
+    /// consensus state of the client. A client can't be created already
+    /// frozen.
+    ///
+    /// When the chain ID of the counterparty is expected, the client state
+    /// has to be for that chain.
+    fn verify_create_client(
+        &self,
+        msg: &MsgCreateClient,
+        expected_chain_id: Option<&ChainId>,
+        checks: &mut ClientChecks,
+    ) -> VpResult<()> {
+        let client_state = AnyClientState::try_from(msg.client_state.clone())
+            .map_err(|e| {
+            Error::ClientError(format!(
+                "Decoding the client state in the message failed: {e}"
+            ))
+        })?;
+        if client_state.is_frozen() {
+            checks.check(Err(Error::ClientError(
+                "The client was created already frozen".to_string(),
+            )))?;
+        }
+        if let Some(expected) = expected_chain_id {
+            checks.check(validate_counterparty_chain_id(
+                &client_state,
+                expected,
+            ))?;
+        }
+        // The prior counter at `u64::MAX` is rejected, since it would overflow
+        // when incremented for the new client
+        let counter = self.client_counter_pre()?;
+        if counter == u64::MAX {
+            return Err(Error::ClientError(format!(
+                "The client counter overflowed: Counter {counter}"
+            )));
+        }
+        let client_id = new_client_id(client_state.client_type(), counter)?;
+        checks.check(self.validate_created_client_keys(&client_id))?;
+        if self.client_state_pre(&client_id)?.is_some() {
+            checks.check(Err(Error::StateChange(format!(
+                "The client already exists: ID {client_id}"
+            ))))?;
+        }
+        let consensus_state = AnyConsensusState::try_from(
+            msg.consensus_state.clone(),
+        )
+        .map_err(|e| {
+            Error::ClientError(format!(
+                "Decoding the consensus state in the message failed: {e}"
+            ))
+        })?;
+        checks.check(validate_nonzero_root(&consensus_state))?;
+        checks.check(validate_nonzero_timestamp(&consensus_state))?;
+        let height = client_state.latest_height();
+        if let Some(stored) = self.consensus_state_post(&client_id, height)? {
+            if !consensus_states_semantically_equal(&consensus_state, &stored) {
+                checks.check(Err(Error::ClientError(format!(
+                    "The stored consensus state conflicts with the one in the \
+                     message: ID {client_id}, Height {height}"
+                ))))?;
+            }
+        }
+        for (height, consensus_state) in
+            self.consensus_states_added(&client_id)?
+        {
+            if !is_same_client_kind(&client_state, &consensus_state) {
+                checks.check(Err(Error::ClientError(format!(
+                    "The consensus state type doesn't match the client type: \
+                     ID {client_id}, Height {height}"
+                ))))?;
+            }
+        }
+        // A new client has only the consensus state at its latest height
+        let heights = self.consensus_state_heights(&client_id)?;
+        if heights != [height] {
+            checks.check(Err(Error::ClientError(format!(
+                "The number of the consensus states of the created client is \
+                 unexpected: ID {client_id}, Count {}",
+                heights.len()
+            ))))?;
+        }
+        Ok(())
+    }
+
+    /// Verify the client states updated by the update message
+    ///
+    /// Unlike a batch of headers, `MsgUpdateClient` carries exactly one
+    /// client message and a transaction has only one IBC message, so an
+    /// update can't contain duplicate headers. Its header is still checked
+    /// as a chain of headers, which is trivially connected.
+    ///
+    /// `MsgUpdateClient` carries no membership proofs. The only height it
+    /// references is the trusted height of the header, whose consensus state
+    /// has to exist in the prior state.
+    ///
+    /// An update which doesn't change the latest height but adds consensus
+    /// states is a consensus state backfill, filling in a gap below the
+    /// latest height. Only the header of a backfill doesn't have to advance
+    /// from the trusted height, as well as the header of a no-op update which
+    /// was already applied, e.g. by another relayer.
+    ///
+    /// A conflicting header, at or below the latest height, freezes the
+    /// client. The frozen client keeps its latest height and gets no new
+    /// consensus state, and the consensus state of the conflicting header
+    /// isn't verified against the stored one.
+    ///
+    /// A client created by the same transaction can't be updated by it.
+    ///
+    /// The checks over the consensus states read only the ones written by the
+    /// transaction and the ones adjacent to them, and the ones at the latest
+    /// heights of the client and at the heights of the header, so that their
+    /// cost doesn't grow with the history of the client. The stored history
+    /// was checked when written.
+    ///
+    /// With `deep_verification`, set by
+    /// [`ClientValidationConfig::deep_verification`], the commitment root of
+    /// the stored consensus state is also checked against the one computed
+    /// from the header.
+    fn verify_update_client(
+        &self,
+        msg: &MsgUpdateClient,
+        deep_verification: bool,
+        checks: &mut ClientChecks,
+    ) -> VpResult<()> {
+        checks.check(self.validate_not_created_in_tx(&msg.client_id))?;
+        checks.check(self.validate_counter_unchanged())?;
+        checks.check(self.assert_only_client_keys_changed(&msg.client_id))?;
+        // A client message which isn't a Tendermint header, e.g. a
+        // misbehaviour, stores no new consensus state and its header checks
+        // are skipped
+        let header = TmHeader::try_from(msg.client_message.clone()).ok();
+        let updated = self.decode_updated_client(
+            &msg.client_id,
+            header
+                .iter()
+                .flat_map(|header| [header.trusted_height, header.height()]),
+        )?;
+        for result in updated_client_checks(&msg.client_id, &updated) {
+            checks.check(result)?;
+        }
+        checks.check(check_consensus_state_revisions(
+            &msg.client_id,
+            updated.posterior.latest_height().revision_number(),
+            updated.consensus_states.iter().map(|(height, _)| *height),
+        ))?;
+        checks.check(check_validator_hash_linkage(
+            &msg.client_id,
+            &updated.consensus_states,
+        ))?;
+        checks.check(
+            self.validate_consensus_states_not_overwritten(&msg.client_id),
+        )?;
+        if let Some(consensus_state) = updated.consensus_state() {
+            checks.check(check_latest_consensus_state_newest(
+                &msg.client_id,
+                consensus_state,
+                &updated.consensus_states,
+            ))?;
+        }
+        let latest_height = updated.prior.latest_height();
+        let added = self.consensus_heights_added(&msg.client_id)?;
+        let is_backfill = updated.posterior.latest_height() == latest_height
+            && !added.is_empty();
+        if is_backfill {
+            checks.check(validate_consensus_state_backfill(
+                &msg.client_id,
+                latest_height,
+                added.iter().copied(),
+            ))?;
+        }
+        // A conflicting header freezes the client instead of updating it
+        let is_freeze =
+            !updated.prior.is_frozen() && updated.posterior.is_frozen();
+        if is_freeze {
+            checks.check(validate_freeze(&msg.client_id, &updated, &added))?;
+        }
+        let header = match header {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+        checks.check(validate_header_chain_ids(
+            &updated.prior,
+            [header.signed_header.header.chain_id.as_str()],
+        ))?;
+        checks.check(validate_connected_headers([(
+            header.trusted_height,
+            header.height(),
+        )]))?;
+        let trusted = self.validate_trusted_consensus_state(
+            &msg.client_id,
+            header.trusted_height,
+        )?;
+        checks.check(validate_trusted_validators_hash(
+            &trusted,
+            &header.trusted_next_validator_set.hash(),
+        ))?;
+        // A header already applied to the prior state is accepted as a no-op
+        // update, e.g. when two relayers submit the same header. A
+        // conflicting header freezing the client doesn't advance either.
+        if !is_backfill
+            && !is_freeze
+            && !self.is_noop_update(&msg.client_id, header.height())?
+        {
+            checks.check(validate_header_height(
+                latest_height,
+                header.height(),
+            ))?;
+        }
+        if let Some(max_clock_drift) = max_clock_drift(&updated.prior) {
+            checks.check(validate_header_timestamp(
+                header.timestamp(),
+                self.block_time()?,
+                max_clock_drift,
+            ))?;
+        }
+        if deep_verification && !is_freeze {
+            checks.check(verify_consensus_state_from_header(
+                &msg.client_id,
+                &header,
+                &updated,
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Check if the update with the header at the given height is a no-op,
+    /// i.e. the consensus state at the header height exists in the prior
+    /// state, and neither it nor the client state is changed
+    fn is_noop_update(
+        &self,
+        client_id: &ClientId,
+        header_height: Height,
+    ) -> VpResult<bool> {
+        let consensus_key = consensus_state_key(client_id, header_height);
+        let prior = self
+            .ctx
+            .read_bytes_pre(&consensus_key)
+            .map_err(Error::NativeVpError)?;
+        if prior.is_none() {
+            return Ok(false);
+        }
+        let posterior = self
+            .ctx
+            .read_bytes_post(&consensus_key)
+            .map_err(Error::NativeVpError)?;
+        let client_key = client_state_key(client_id);
+        let is_client_unchanged = self
+            .ctx
+            .read_bytes_pre(&client_key)
+            .map_err(Error::NativeVpError)?
+            == self
+                .ctx
+                .read_bytes_post(&client_key)
+                .map_err(Error::NativeVpError)?;
+        Ok(prior == posterior && is_client_unchanged)
+    }
+
+    /// Check that the consensus state at the trusted height referenced by a
+    /// header exists in the prior state, and return it
+    fn validate_trusted_consensus_state(
+        &self,
+        client_id: &ClientId,
+        trusted_height: Height,
+    ) -> VpResult<AnyConsensusState> {
+        self.consensus_state_pre(client_id, trusted_height)?
+            .ok_or_else(|| {
+                Error::ProofVerificationError(format!(
+                    "The consensus state at the trusted height doesn't exist: \
+                     ID {client_id}, Trusted height {trusted_height}"
+                ))
+            })
+    }
+
+    /// Verify that the proofs of the upgrade message are verified against
+    /// the upgrade path configured in the prior client state
+    ///
+    /// A message without actual proofs is rejected early, before reading any
+    /// client data. The upgrade can't change the client type.
+    fn verify_upgrade_client(
+        &self,
+        msg: &MsgUpgradeClient,
+        config: &ClientValidationConfig,
+        checks: &mut ClientChecks,
+    ) -> VpResult<()> {
+        let max_proof_size = config.max_upgrade_proof_size;
+        checks.check(proof_upgrade_client(msg, max_proof_size).map(|_| ()))?;
+        checks.check(
+            proof_upgrade_consensus_state(msg, max_proof_size).map(|_| ()),
+        )?;
+        checks.check(validate_upgraded_client_version(msg))?;
+        checks.check(self.validate_counter_unchanged())?;
+        let client_id = &msg.client_id;
+        let prior = self.client_state_pre(client_id)?.ok_or_else(|| {
+            Error::ClientError(format!(
+                "The prior client state doesn't exist: ID {client_id}"
+            ))
+        })?;
+        let posterior =
+            self.client_state_post(client_id)?.ok_or_else(|| {
+                Error::ClientError(format!(
+                    "The posterior client state doesn't exist: ID {client_id}"
+                ))
+            })?;
+        if posterior.client_type() != prior.client_type() {
+            checks.check(Err(Error::ClientError(format!(
+                "The client type was changed by the upgrade: ID {client_id}, \
+                 Prior {}, Posterior {}",
+                prior.client_type(),
+                posterior.client_type()
+            ))))?;
+        }
+        checks.check(self.validate_consensus_history(
+            client_id,
+            posterior.latest_height(),
+            config.upgrade_consensus_history,
+        ))?;
+        // A client type without an upgrade path has no proofs to verify, but
+        // its upgraded states are still checked
+        if let Some(upgrade_path) = upgrade_path(&prior) {
+            checks.check(self.verify_upgrade_proofs(
+                msg,
+                &prior,
+                &upgrade_path,
+                max_proof_size,
+            ))?;
+        }
+        checks.check(self.validate_upgraded_states(
+            msg,
+            &prior,
+            config.strict_upgrade_equality,
+        ))
+    }
+
+    /// Verify the proofs of the upgrade message against the commitment root
+    /// of the prior consensus state at the latest height of the prior client
+    /// state. The posterior consensus state at that height is written by the
+    /// transaction itself, so it can't anchor the proofs.
+    ///
+    /// Only the proofs constructed against another upgrade path are reported
+    /// as a wrong upgrade path. Any other failure of the verification keeps
+    /// its own error.
+    fn verify_upgrade_proofs(
+        &self,
+        msg: &MsgUpgradeClient,
+        prior: &AnyClientState,
+        upgrade_path: &[String],
+        max_proof_size: usize,
+    ) -> VpResult<()> {
+        let client_id = &msg.client_id;
+        if upgrade_path.is_empty() {
+            return Err(Error::ProofVerificationError(format!(
+                "The upgrade path is wrong: No upgrade path is set for the \
+                 client {client_id}"
+            )));
+        }
+        for proof in [
+            proof_upgrade_client(msg, max_proof_size)?,
+            proof_upgrade_consensus_state(msg, max_proof_size)?,
+        ] {
+            validate_proof_upgrade_path(client_id, &proof, upgrade_path)?;
+        }
+        let height = prior.latest_height();
+        let root = match self.consensus_state_pre(client_id, height)? {
+            Some(AnyConsensusState::Tendermint(cs)) => cs.inner().root.clone(),
+            _ => {
+                return Err(Error::ProofVerificationError(format!(
+                    "The proofs aren't anchored to a prior root: The prior \
+                     Tendermint consensus state doesn't exist: ID \
+                     {client_id}, Height {height}"
+                )));
+            }
+        };
+        prior
+            .verify_upgrade_client(
+                msg.upgraded_client_state.clone(),
+                msg.upgraded_consensus_state.clone(),
+                msg.proof_upgrade_client.clone(),
+                msg.proof_upgrade_consensus_state.clone(),
+                &root,
+            )
+            .map_err(|e| {
+                Error::ProofVerificationError(format!(
+                    "Verifying the upgrade proofs failed: ID {client_id}, \
+                     Error {e}"
+                ))
+            })
+    }
+
+    /// Check that the prior consensus states of the upgraded client are kept
+    /// or removed in the posterior state according to the policy. The
+    /// consensus state at the upgraded height isn't part of the history.
+    pub fn validate_consensus_history(
+        &self,
+        client_id: &ClientId,
+        upgraded_height: Height,
+        policy: ConsensusHistoryPolicy,
+    ) -> VpResult<()> {
+        let prefix = consensus_state_prefix(client_id);
+        let pre = self.ctx.pre();
+        let iter = storage_api::iter_prefix_bytes(&pre, &prefix)
+            .map_err(Error::NativeVpError)?;
+        for result in iter {
+            let (key, _) = result.map_err(Error::NativeVpError)?;
+            let height = parse_consensus_height(&key)?;
+            if height == upgraded_height {
+                continue;
+            }
+            let retained =
+                self.ctx.has_key_post(&key).map_err(Error::NativeVpError)?;
+            let violated = match policy {
+                ConsensusHistoryPolicy::Retain => !retained,
+                ConsensusHistoryPolicy::Prune => retained,
+            };
+            if violated {
+                return Err(Error::ClientError(format!(
+                    "The upgrade violates the consensus history policy: ID \
+                     {client_id}, Policy {policy:?}, Height {height}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the stored client and consensus states are the upgraded
+    /// ones in the upgrade message
+    ///
+    /// Only the fields chosen by the counterparty chain are compared with the
+    /// upgraded states, because the handler keeps the fields chosen by the
+    /// relayer, e.g. the trusting period, from the prior client state and
+    /// stores a sentinel commitment root in the upgraded consensus state.
+    /// With `strict`, the stored states also have to keep the fields of the
+    /// prior client state and the sentinel root, and the client can't be
+    /// frozen.
+    fn validate_upgraded_states(
+        &self,
+        msg: &MsgUpgradeClient,
+        prior: &AnyClientState,
+        strict: bool,
+    ) -> VpResult<()> {
+        let client_id = &msg.client_id;
+        let upgraded_client_state =
+            AnyClientState::try_from(msg.upgraded_client_state.clone())
+                .map_err(|e| {
+                    Error::ClientError(format!(
+                        "Decoding the upgraded client state failed: {e}"
+                    ))
+                })?;
+        let upgraded_consensus_state =
+            AnyConsensusState::try_from(msg.upgraded_consensus_state.clone())
+                .map_err(|e| {
+                Error::ClientError(format!(
+                    "Decoding the upgraded consensus state failed: {e}"
+                ))
+            })?;
+        let client_state =
+            self.client_state_post(client_id)?.ok_or_else(|| {
+                Error::ClientError(format!(
+                    "The posterior client state doesn't exist: ID {client_id}"
+                ))
+            })?;
+        let height = client_state.latest_height();
+        let consensus_state = self
+            .consensus_state_post(client_id, height)?
+            .ok_or_else(|| {
+                Error::ClientError(format!(
+                    "The posterior consensus state doesn't exist: ID \
+                     {client_id}, Height {height}"
+                ))
+            })?;
+        let mut client_state_equal = client_states_semantically_equal(
+            &client_state,
+            &upgraded_client_state,
+        );
+        let mut consensus_state_equal = upgraded_consensus_states_equal(
+            &consensus_state,
+            &upgraded_consensus_state,
+        );
+        if strict {
+            client_state_equal &=
+                upgraded_client_state_kept_prior(prior, &client_state);
+            consensus_state_equal &= has_sentinel_root(&consensus_state);
+        }
+        if !client_state_equal {
+            return Err(Error::ClientError(format!(
+                "The stored client state isn't the upgraded one: ID \
+                 {client_id}"
+            )));
+        }
+        if !consensus_state_equal {
+            return Err(Error::ClientError(format!(
+                "The stored consensus state isn't the upgraded one: ID \
+                 {client_id}, Height {height}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Verify the ICS-23 membership proof of the value at the path in the
+    /// counterparty state committed in the consensus state of the client at
+    /// the given height
+    ///
+    /// The prior client and consensus states are used because they are the
+    /// ones trusted before the transaction.
+    pub fn verify_membership(
+        &self,
+        client_id: &ClientId,
+        prefix: &CommitmentPrefix,
+        proof: &MerkleProof,
+        path: &Path,
+        value: &[u8],
+        height: Height,
+    ) -> VpResult<()> {
+        let client_state =
+            self.client_state_pre(client_id)?.ok_or_else(|| {
+                Error::ClientError(format!(
+                    "The prior client state doesn't exist: ID {client_id}"
+                ))
+            })?;
+        let consensus_state = self
+            .consensus_state_pre(client_id, height)?
+            .ok_or_else(|| {
+                Error::ClientError(format!(
+                    "The prior consensus state doesn't exist: ID {client_id}, \
+                     Height {height}"
+                ))
+            })?;
+        let proof =
+            CommitmentProofBytes::try_from(proof.clone()).map_err(|e| {
+                Error::ProofVerificationError(format!(
+                    "Encoding the membership proof failed: Path {path}, Error \
+                     {e}"
+                ))
+            })?;
+        client_state
+            .verify_membership(
+                prefix,
+                &proof,
+                consensus_state.root(),
+                path.clone(),
+                value.to_vec(),
+            )
+            .map_err(|e| {
+                Error::ProofVerificationError(format!(
+                    "The membership proof isn't verified: ID {client_id}, \
+                     Height {height}, Path {path}, Error {e}"
+                ))
+            })
+    }
+
+    /// Read and decode the states of the updated client once, so that the
+    /// checks can share them. Only the consensus states written by the
+    /// transaction, at the latest heights of the client and at the given
+    /// heights referenced by the update are read.
+    fn decode_updated_client(
+        &self,
+        client_id: &ClientId,
+        referenced_heights: impl IntoIterator<Item = Height>,
+    ) -> VpResult<UpdatedClient> {
+        // A client which doesn't exist in the prior state has to be created
+        // instead of updated
+        let prior =
+            self.client_state_any(client_id, false)?.ok_or_else(|| {
+                Error::ClientError(format!(
+                    "The updated client doesn't exist: ID {client_id}"
+                ))
+            })?;
+        let posterior =
+            self.client_state_any(client_id, true)?.ok_or_else(|| {
+                Error::ClientError(format!(
+                    "The posterior client state doesn't exist: ID {client_id}"
+                ))
+            })?;
+        let prior_type_url = prior.type_url.clone();
+        let posterior_type_url = posterior.type_url.clone();
+        let key = client_state_key(client_id);
+        let prior = client_state_from_any(&key, prior)?;
+        let posterior = client_state_from_any(&key, posterior)?;
+        // The adjacent states are read to check the validator hash linkage
+        let mut heights = self.consensus_heights_around_written(client_id)?;
+        heights.extend([prior.latest_height(), posterior.latest_height()]);
+        heights.extend(referenced_heights);
+        let consensus_states =
+            self.consensus_states_at_heights(client_id, heights)?;
+        Ok(UpdatedClient {
+            prior,
+            posterior,
+            prior_type_url,
+            posterior_type_url,
+            consensus_states,
+        })
+    }
+
+    /// Get the time of the current block
+    fn block_time(&self) -> VpResult<Timestamp> {
+        let height =
+            self.ctx.get_block_height().map_err(Error::NativeVpError)?;
+        let header = self
+            .ctx
+            .get_block_header(height)
+            .map_err(Error::NativeVpError)?
+            .ok_or_else(|| {
+                Error::ClientError(format!(
+                    "The block header doesn't exist: Height {height}"
+                ))
+            })?;
+        let time = TmTime::try_from(header.time).map_err(|_| {
+            Error::ClientError(format!(
+                "Converting the block time failed: Height {height}"
+            ))
+        })?;
+        Ok(time.into())
+    }
+
+    /// Get the number of bytes stored for the client in the posterior state:
+    /// the client state including its client type, all the consensus states,
+    /// the update time and height, and the client counter
+    pub fn client_storage_size(&self, client_id: &ClientId) -> VpResult<usize> {
+        let mut size = 0;
+        for key in [
+            client_state_key(client_id),
+            client_update_timestamp_key(client_id),
+            client_update_height_key(client_id),
+            client_counter_key(),
+        ] {
+            size += self
+                .ctx
+                .read_bytes_post(&key)
+                .map_err(Error::NativeVpError)?
+                .map_or(0, |bytes| bytes.len());
+        }
+        let prefix = consensus_state_prefix(client_id);
+        let post = self.ctx.post();
+        let iter = storage_api::iter_prefix_bytes(&post, &prefix)
+            .map_err(Error::NativeVpError)?;
+        for result in iter {
+            let (_, bytes) = result.map_err(Error::NativeVpError)?;
+            size += bytes.len();
+        }
+        Ok(size)
+    }
+
+    /// Get the client state in the posterior state
+    pub fn client_state_post(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<Option<AnyClientState>> {
+        query_client_state(&self.ctx.post(), client_id)
+    }
+
+    /// Get the client state as the stored `Any` in the posterior state, or
+    /// in the prior state if `post` is false, e.g. to compare the type URLs
+    /// the client states were stored with
+    fn client_state_any(
+        &self,
+        client_id: &ClientId,
+        post: bool,
+    ) -> VpResult<Option<Any>> {
+        let key = client_state_key(client_id);
+        let bytes = if post {
+            self.ctx.read_bytes_post(&key)
+        } else {
+            self.ctx.read_bytes_pre(&key)
+        };
+        bytes
+            .map_err(Error::NativeVpError)?
+            .map(|bytes| decode_client_state_any(&key, &bytes))
+            .transpose()
+    }
+
+    /// Get the consensus state at the given height in the posterior state
+    pub fn consensus_state_post(
+        &self,
+        client_id: &ClientId,
+        height: Height,
+    ) -> VpResult<Option<AnyConsensusState>> {
+        let key = consensus_state_key(client_id, height);
+        self.ctx
+            .read_bytes_post(&key)
+            .map_err(Error::NativeVpError)?
+            .map(|bytes| decode_consensus_state(&key, bytes))
+            .transpose()
+    }
+
+    /// Get the consensus state at the given height in the prior state
+    pub fn consensus_state_pre(
+        &self,
+        client_id: &ClientId,
+        height: Height,
+    ) -> VpResult<Option<AnyConsensusState>> {
+        let key = consensus_state_key(client_id, height);
+        self.ctx
+            .read_bytes_pre(&key)
+            .map_err(Error::NativeVpError)?
+            .map(|bytes| decode_consensus_state(&key, bytes))
+            .transpose()
+    }
+
+    /// Get the undecoded consensus state at the given height in the
+    /// posterior state, or in the prior state if `post` is false, e.g. to
+    /// compute a commitment over the raw bytes
+    pub fn consensus_state_bytes(
+        &self,
+        client_id: &ClientId,
+        height: Height,
+        post: bool,
+    ) -> VpResult<Option<Vec<u8>>> {
+        let key = consensus_state_key(client_id, height);
+        let bytes = if post {
+            self.ctx.read_bytes_post(&key)
+        } else {
+            self.ctx.read_bytes_pre(&key)
+        };
+        bytes.map_err(Error::NativeVpError)
+    }
+
+    /// Get the consensus states at the given heights in the prior state,
+    /// reading them in one pass over the consensus states of the client
+    /// instead of one read per height
+    pub fn consensus_states_at(
+        &self,
+        client_id: &ClientId,
+        heights: &[Height],
+    ) -> VpResult<Vec<Option<AnyConsensusState>>> {
+        let prefix = consensus_state_prefix(client_id);
+        let pre = self.ctx.pre();
+        let iter = storage_api::iter_prefix_bytes(&pre, &prefix)
+            .map_err(Error::NativeVpError)?;
+        let mut found = HashMap::new();
+        for result in iter {
+            let (key, bytes) = result.map_err(Error::NativeVpError)?;
+            let height = parse_consensus_height(&key)?;
+            if heights.contains(&height) {
+                found.insert(height, (key, bytes));
+            }
+        }
+        heights
+            .iter()
+            .map(|height| {
+                found
+                    .get(height)
+                    .map(|(key, bytes)| {
+                        decode_consensus_state(key, bytes.clone())
+                    })
+                    .transpose()
+            })
+            .collect()
+    }
+
+    /// Get the client state in the prior state
+    pub fn client_state_pre(
+        &self,
+        client_id: &ClientId,
+    ) -> VpResult<Option<AnyClientState>> {
+        query_client_state(&self.ctx.pre(), client_id)
+    }
+}
+
+/// Read the client state from the storage, e.g. to answer a client state
+/// query. The VP reads the client states with this function too, so that
+/// the queries and the validation derive the key and decode the state in
+/// the same way.
+pub fn query_client_state<S>(
+    storage: &S,
+    client_id: &ClientId,
+) -> VpResult<Option<AnyClientState>>
+where
+    S: StorageRead,
+{
+    let key = client_state_key(client_id);
+    storage
+        .read_bytes(&key)
+        .map_err(Error::NativeVpError)?
+        .map(|bytes| decode_client_state(&key, &bytes))
+        .transpose()
+}
+
+/// The states of an updated client, decoded once per validation
+struct UpdatedClient {
+    /// The client state in the prior state
+    prior: AnyClientState,
+    /// The client state in the posterior state
+    posterior: AnyClientState,
+    /// The type URL the prior client state was stored with
+    prior_type_url: String,
+    /// The type URL the posterior client state was stored with
+    posterior_type_url: String,
+    /// The posterior consensus states of the client read by the update,
+    /// ordered by their heights, shared by the checks over the consensus
+    /// states
+    consensus_states: Vec<(Height, AnyConsensusState)>,
+}
+
+impl UpdatedClient {
+    /// Get the posterior consensus state at the latest height of the
+    /// posterior client state
+    fn consensus_state(&self) -> Option<&AnyConsensusState> {
+        self.consensus_state_at(self.posterior.latest_height())
+    }
+
+    /// Get the posterior consensus state at the given height
+    fn consensus_state_at(&self, height: Height) -> Option<&AnyConsensusState> {
+        self.consensus_states
+            .binary_search_by_key(&height, |(height, _)| *height)
+            .ok()
+            .map(|index| &self.consensus_states[index].1)
+    }
+}
+
+/// The results of the client checks, shared by the fail-fast and the
+/// verbose validation. An error of an independent check is either returned
+/// at once or collected. The errors of the steps which the following checks
+/// depend on, e.g. decoding the states, always stop the checks.
+#[derive(Debug, Default)]
+struct ClientChecks {
+    /// Whether the errors of the independent checks are collected instead
+    /// of being returned
+    collect: bool,
+    /// The collected errors
+    errors: Vec<Error>,
+}
+
+impl ClientChecks {
+    /// Checks failing on the first error
+    fn fail_fast() -> Self {
+        Self::default()
+    }
+
+    /// Checks collecting the errors of all the independent checks
+    fn collecting() -> Self {
+        Self {
+            collect: true,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Record the result of an independent check. The error is returned
+    /// unless the errors are collected.
+    fn check(&mut self, result: VpResult<()>) -> VpResult<()> {
+        match result {
+            Err(e) if self.collect => {
+                self.errors.push(e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Get all the errors, including the one which stopped the checks
+    fn into_errors(mut self, result: VpResult<()>) -> Vec<Error> {
+        self.errors.extend(result.err());
+        self.errors
+    }
+}
+
+/// Run all the independent checks of the decoded states of the updated
+/// client. This doesn't access the storage.
+fn updated_client_checks(
+    client_id: &ClientId,
+    updated: &UpdatedClient,
+) -> [VpResult<()>; 8] {
+    [
+        validate_embedded_client_id(client_id, &updated.prior),
+        validate_embedded_client_id(client_id, &updated.posterior),
+        validate_no_rollback(
+            client_id,
+            updated.prior.latest_height(),
+            updated.posterior.latest_height(),
+        ),
+        validate_client_type_unchanged(
+            &updated.prior_type_url,
+            &updated.posterior_type_url,
+        ),
+        validate_proof_specs_unchanged(&updated.prior, &updated.posterior),
+        updated
+            .consensus_state()
+            .map_or(Ok(()), validate_next_validators_hash),
+        updated
+            .consensus_state()
+            .map_or(Ok(()), validate_nonzero_root),
+        updated
+            .consensus_state()
+            .map_or(Ok(()), validate_nonzero_timestamp),
+    ]
+}
+
+/// Check that the consensus state at the latest height of the client has
+/// the newest timestamp of all the given consensus states of the client
+fn check_latest_consensus_state_newest(
+    client_id: &ClientId,
+    latest: &AnyConsensusState,
+    consensus_states: &[(Height, AnyConsensusState)],
+) -> VpResult<()> {
+    let latest_timestamp = latest.timestamp();
+    let newer = consensus_states.iter().find(|(_, consensus_state)| {
+        consensus_state.timestamp().nanoseconds()
+            > latest_timestamp.nanoseconds()
+    });
+    if let Some((height, consensus_state)) = newer {
+        return Err(Error::HeaderError(format!(
+            "The latest consensus state isn't the newest: ID {client_id}, \
+             Latest timestamp {latest_timestamp}, Height {height} at {}",
+            consensus_state.timestamp()
+        )));
+    }
+    Ok(())
+}
+
+/// Check that the consensus state stored for the header height has the
+/// commitment root computed from the header. The consensus state is taken
+/// from the ones already decoded for the update.
+fn verify_consensus_state_from_header(
+    client_id: &ClientId,
+    header: &TmHeader,
+    updated: &UpdatedClient,
+) -> VpResult<()> {
+    let height = header.height();
+    let expected = TmConsensusStateType::from(header.clone()).root;
+    validate_consensus_state_root(
+        updated.consensus_state_at(height),
+        client_id,
+        height,
+        &expected,
+    )
+}
+
+/// Check that all the given heights of the consensus states of the client
+/// are of the given revision number of the client
+fn check_consensus_state_revisions(
+    client_id: &ClientId,
+    revision_number: u64,
+    heights: impl IntoIterator<Item = Height>,
+) -> VpResult<()> {
+    for height in heights {
+        if height.revision_number() != revision_number {
+            return Err(Error::ClientError(format!(
+                "The consensus state revision doesn't match the client: ID \
+                 {client_id}, Client revision {revision_number}, Height \
+                 {height}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check that the consensus states of the client at adjacent heights don't
+/// have contradictory validator hashes. The consensus states have to be
+/// ordered by their heights.
+fn check_validator_hash_linkage(
+    client_id: &ClientId,
+    consensus_states: &[(Height, AnyConsensusState)],
+) -> VpResult<()> {
+    for pair in consensus_states.windows(2) {
+        let (prev_height, prev) = &pair[0];
+        let (height, cs) = &pair[1];
+        let (prev, cs) = match (prev, cs) {
+            (
+                AnyConsensusState::Tendermint(prev),
+                AnyConsensusState::Tendermint(cs),
+            ) => (prev.inner(), cs.inner()),
+            // Only the Tendermint consensus states have validator hashes
+            #[allow(unreachable_patterns)]
+            _ => continue,
+        };
+        let is_adjacent = prev_height.increment() == *height;
+        if is_adjacent
+            && prev.root == cs.root
+            && prev.next_validators_hash != cs.next_validators_hash
+        {
+            return Err(Error::HeaderError(format!(
+                "The validator hashes of the adjacent consensus states \
+                 contradict: ID {client_id}, Height {prev_height} with {}, \
+                 Height {height} with {}",
+                prev.next_validators_hash, cs.next_validators_hash
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check that the latest height of the client isn't lowered by the update
+///
+/// Heights are ordered by their revision number first, then by their height
+/// in the revision, so a height of a newer revision is always greater.
+fn validate_no_rollback(
+    client_id: &ClientId,
+    prior_height: Height,
+    posterior_height: Height,
+) -> VpResult<()> {
+    if posterior_height < prior_height {
+        return Err(Error::ClientError(format!(
+            "The client height was rolled back: ID {client_id}, Prior height \
+             {prior_height}, Posterior height {posterior_height}"
+        )));
+    }
+    Ok(())
+}
+
+/// Check that the trusted validator set of a header is the next validator set
+/// of the consensus state at the trusted height. The client verifies the
+/// header against it later, but a mismatch is reported here more clearly.
+fn validate_trusted_validators_hash(
+    trusted: &AnyConsensusState,
+    trusted_validators_hash: &TmHash,
+) -> VpResult<()> {
+    match trusted {
+        AnyConsensusState::Tendermint(cs)
+            if cs.inner().next_validators_hash != *trusted_validators_hash =>
+        {
+            Err(Error::HeaderError(format!(
+                "The trusted validator set mismatched: Consensus state {}, \
+                 Header {trusted_validators_hash}",
+                cs.inner().next_validators_hash
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check that the client state is for the expected counterparty chain. A
+/// client type without a chain ID never matches.
+fn validate_counterparty_chain_id(
+    client_state: &AnyClientState,
+    expected: &ChainId,
+) -> VpResult<()> {
+    let chain_id = match client_state {
+        AnyClientState::Tendermint(cs) => Some(&cs.inner().chain_id),
+        // The mock client state for testing
+        #[allow(unreachable_patterns)]
+        _ => None,
+    };
+    if chain_id != Some(expected) {
+        return Err(Error::ClientError(format!(
+            "The counterparty chain ID is unexpected: Expected {expected}, \
+             Client chain ID {}",
+            chain_id.map_or_else(|| "none".to_string(), ChainId::to_string)
+        )));
+    }
+    Ok(())
+}
+
+/// Check that all the headers, given by their chain IDs, belong to the chain
+/// of the client. The check is a no-op for client types without a chain ID.
+fn validate_header_chain_ids<'a>(
+    client_state: &AnyClientState,
+    header_chain_ids: impl IntoIterator<Item = &'a str>,
+) -> VpResult<()> {
+    let chain_id = match client_state {
+        AnyClientState::Tendermint(cs) => cs.inner().chain_id.as_str(),
+        // The mock client state for testing
+        #[allow(unreachable_patterns)]
+        _ => return Ok(()),
+    };
+    for (index, header_chain_id) in header_chain_ids.into_iter().enumerate() {
+        if header_chain_id != chain_id {
+            return Err(Error::HeaderError(format!(
+                "The header {index} belongs to another chain: Client chain ID \
+                 {chain_id}, Header chain ID {header_chain_id}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check that the headers, given by their trusted heights and their heights,
+/// form a connected chain, i.e. each header is trusted at a height below its
+/// own and at the height of the previous one. The first header is trusted
+/// at a consensus state of the client, which is checked separately.
+fn validate_connected_headers(
+    headers: impl IntoIterator<Item = (Height, Height)>,
+) -> VpResult<()> {
+    let mut previous_height = None;
+    for (index, (trusted_height, height)) in headers.into_iter().enumerate() {
+        if trusted_height >= height {
+            return Err(Error::HeaderError(format!(
+                "The header chain is disconnected: The header {index} is \
+                 trusted at or above its height: Trusted height \
+                 {trusted_height}, Height {height}"
+            )));
+        }
+        if let Some(previous_height) = previous_height {
+            if trusted_height != previous_height {
+                return Err(Error::HeaderError(format!(
+                    "The header chain is disconnected: The header {index} \
+                     isn't trusted at the height of the previous header: \
+                     Trusted height {trusted_height}, Previous height \
+                     {previous_height}"
+                )));
+            }
+        }
+        previous_height = Some(height);
+    }
+    Ok(())
+}
+
+/// Check that the header height is strictly greater than the latest height
+/// trusted by the prior client state
+fn validate_header_height(
+    trusted_height: Height,
+    header_height: Height,
+) -> VpResult<()> {
+    if header_height <= trusted_height {
+        return Err(Error::HeaderError(format!(
+            "The header doesn't advance from the trusted height: Trusted \
+             height {trusted_height}, Header height {header_height}"
+        )));
+    }
+    Ok(())
+}
+
+/// Check that all the consensus states added by a backfill are below the
+/// latest height of the client
+fn validate_consensus_state_backfill(
+    client_id: &ClientId,
+    latest_height: Height,
+    added_heights: impl IntoIterator<Item = Height>,
+) -> VpResult<()> {
+    for height in added_heights {
+        if height >= latest_height {
+            return Err(Error::ClientError(format!(
+                "Invalid consensus state backfill: A consensus state was \
+                 added at or above the latest height without updating the \
+                 client: ID {client_id}, Latest height {latest_height}, \
+                 Height {height}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check that an update freezing the client neither adds a consensus state
+/// nor changes the latest height of the client
+fn validate_freeze(
+    client_id: &ClientId,
+    updated: &UpdatedClient,
+    added_heights: &[Height],
+) -> VpResult<()> {
+    let prior_height = updated.prior.latest_height();
+    let posterior_height = updated.posterior.latest_height();
+    if !added_heights.is_empty() || posterior_height != prior_height {
+        return Err(Error::ClientError(format!(
+            "The client was updated while being frozen: ID {client_id}, Prior \
+             height {prior_height}, Posterior height {posterior_height}, \
+             Added heights {added_heights:?}"
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the max clock drift configured in the client state, if the
+/// client type has one
+fn max_clock_drift(client_state: &AnyClientState) -> Option<Duration> {
+    match client_state {
+        AnyClientState::Tendermint(cs) => Some(cs.inner().max_clock_drift),
+        // The mock client state for testing
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// Check that the header timestamp doesn't exceed the block time plus the
+/// max clock drift of the client
+fn validate_header_timestamp(
+    header_time: Timestamp,
+    block_time: Timestamp,
+    max_clock_drift: Duration,
+) -> VpResult<()> {
+    let max_clock_drift_nanos =
+        u64::try_from(max_clock_drift.as_nanos()).unwrap_or(u64::MAX);
+    let limit = block_time
+        .nanoseconds()
+        .saturating_add(max_clock_drift_nanos);
+    if header_time.nanoseconds() > limit {
+        return Err(Error::HeaderError(format!(
+            "The header is too far in the future: Header time {header_time}, \
+             Block time {block_time}, Max clock drift {max_clock_drift:?}"
+        )));
+    }
+    Ok(())
+}
+
+/// Returns true if the consensus state is of the type of the client state.
+/// The only types are Tendermint and the mock for testing.
+fn is_same_client_kind(
+    client_state: &AnyClientState,
+    consensus_state: &AnyConsensusState,
+) -> bool {
+    matches!(client_state, AnyClientState::Tendermint(_))
+        == matches!(consensus_state, AnyConsensusState::Tendermint(_))
+}
+
+/// Returns true if the consensus states are the same. The metadata of an
+/// update, i.e. the processed time and height, is stored separately and
+/// isn't compared.
+fn consensus_states_semantically_equal(
+    a: &AnyConsensusState,
+    b: &AnyConsensusState,
+) -> bool {
+    a.encode_vec() == b.encode_vec()
+}
+
+/// Returns true if the client states have the same fields chosen by the
+/// counterparty chain. The fields chosen by the relayer, e.g. the trusting
+/// period, aren't compared.
+fn client_states_semantically_equal(
+    a: &AnyClientState,
+    b: &AnyClientState,
+) -> bool {
+    match (a, b) {
+        (AnyClientState::Tendermint(a), AnyClientState::Tendermint(b)) => {
+            let (a, b) = (a.inner(), b.inner());
+            a.chain_id == b.chain_id
+                && a.unbonding_period == b.unbonding_period
+                && a.latest_height == b.latest_height
+                && a.proof_specs == b.proof_specs
+                && a.upgrade_path == b.upgrade_path
+        }
+        // The mock client state for testing
+        #[allow(unreachable_patterns)]
+        _ => Any::from(a.clone()) == Any::from(b.clone()),
+    }
+}
+
+/// Returns true if the stored consensus state is the upgraded one. The
+/// commitment root isn't compared because a sentinel root is stored for
+/// the upgraded consensus state.
+fn upgraded_consensus_states_equal(
+    stored: &AnyConsensusState,
+    upgraded: &AnyConsensusState,
+) -> bool {
+    match (stored, upgraded) {
+        (
+            AnyConsensusState::Tendermint(stored),
+            AnyConsensusState::Tendermint(upgraded),
+        ) => {
+            let (stored, upgraded) = (stored.inner(), upgraded.inner());
+            stored.timestamp == upgraded.timestamp
+                && stored.next_validators_hash == upgraded.next_validators_hash
+        }
+        // The mock consensus state for testing
+        #[allow(unreachable_patterns)]
+        _ => consensus_states_semantically_equal(stored, upgraded),
+    }
+}
+
+/// Returns true if the stored client state of the upgraded client keeps the
+/// fields chosen by the relayer in the prior client state, and isn't frozen
+fn upgraded_client_state_kept_prior(
+    prior: &AnyClientState,
+    stored: &AnyClientState,
+) -> bool {
+    match (prior, stored) {
+        (
+            AnyClientState::Tendermint(prior),
+            AnyClientState::Tendermint(stored),
+        ) => {
+            let (prior, stored) = (prior.inner(), stored.inner());
+            prior.trust_level == stored.trust_level
+                && prior.trusting_period == stored.trusting_period
+                && prior.max_clock_drift == stored.max_clock_drift
+                && prior.allow_update == stored.allow_update
+                && stored.frozen_height.is_none()
+        }
+        // The mock client state has no field chosen by the relayer
+        #[allow(unreachable_patterns)]
+        _ => !stored.is_frozen(),
+    }
+}
+
+/// Returns true if the stored consensus state of the upgraded client has the
+/// sentinel commitment root stored by the handler
+fn has_sentinel_root(stored: &AnyConsensusState) -> bool {
+    match stored {
+        AnyConsensusState::Tendermint(stored) => {
+            stored.inner().root == CommitmentRoot::from_bytes(SENTINEL_ROOT)
+        }
+        // The mock consensus state is stored as upgraded
+        #[allow(unreachable_patterns)]
+        _ => true,
+    }
+}
+
+/// Check that the consensus state at the given height has the expected
+/// commitment root
+fn validate_consensus_state_root(
+    consensus_state: Option<&AnyConsensusState>,
+    client_id: &ClientId,
+    height: Height,
+    expected: &CommitmentRoot,
+) -> VpResult<()> {
+    let stored = match consensus_state {
+        Some(AnyConsensusState::Tendermint(cs)) => &cs.inner().root,
+        #[allow(unreachable_patterns)]
+        Some(_) => {
+            return Err(Error::HeaderError(format!(
+                "The consensus state for the Tendermint header isn't a \
+                 Tendermint consensus state: ID {client_id}, Height {height}"
+            )));
+        }
+        None => {
+            return Err(Error::HeaderError(format!(
+                "The consensus state for the header doesn't exist: ID \
+                 {client_id}, Height {height}"
+            )));
+        }
+    };
+    if stored != expected {
+        return Err(Error::HeaderError(format!(
+            "The consensus root doesn't match the header: The root of the \
+             stored consensus state isn't the app hash of the header: ID \
+             {client_id}, Height {height}"
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the client ID embedded in the client state, if the client type
+/// embeds one.
+///
+/// Neither the Tendermint nor the mock client state embeds its client ID,
+/// so the stored state can't be cross-checked against the client ID in the
+/// message and the check is a no-op for them.
+fn embedded_client_id(client_state: &AnyClientState) -> Option<ClientId> {
+    match client_state {
+        AnyClientState::Tendermint(_) => None,
+        // The mock client state for testing
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// Check that the client ID embedded in the client state, if any, is the
+/// given client ID
+fn validate_embedded_client_id(
+    client_id: &ClientId,
+    client_state: &AnyClientState,
+) -> VpResult<()> {
+    match embedded_client_id(client_state) {
+        Some(embedded) if embedded != *client_id => {
+            Err(Error::ClientError(format!(
+                "The client state belongs to another client: ID {client_id}, \
+                 Embedded ID {embedded}"
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Returns the proof of the upgraded client state in the upgrade message,
+/// which can't be larger than the maximum size
+fn proof_upgrade_client(
+    msg: &MsgUpgradeClient,
+    max_size: usize,
+) -> VpResult<MerkleProof> {
+    let name = "upgraded client state";
+    validate_proof_size(name, &msg.proof_upgrade_client, max_size)?;
+    decode_merkle_proof(name, &msg.proof_upgrade_client)
+}
+
+/// Returns the proof of the upgraded consensus state in the upgrade
+/// message, which can't be larger than the maximum size
+fn proof_upgrade_consensus_state(
+    msg: &MsgUpgradeClient,
+    max_size: usize,
+) -> VpResult<MerkleProof> {
+    let name = "upgraded consensus state";
+    validate_proof_size(name, &msg.proof_upgrade_consensus_state, max_size)?;
+    decode_merkle_proof(name, &msg.proof_upgrade_consensus_state)
+}
+
+/// Check that the proof isn't larger than the maximum size, before decoding
+/// it
+fn validate_proof_size(
+    name: &str,
+    proof: &CommitmentProofBytes,
+    max_size: usize,
+) -> VpResult<()> {
+    let size = proof.as_ref().len();
+    if size > max_size {
+        return Err(Error::ProofVerificationError(format!(
+            "The proof is too large: The proof of the {name} has {size} \
+             bytes, Max size {max_size}"
+        )));
+    }
+    Ok(())
+}
+
+/// Decode the proof bytes into a Merkle proof, which has to be non-empty
+fn decode_merkle_proof(
+    name: &str,
+    proof: &CommitmentProofBytes,
+) -> VpResult<MerkleProof> {
+    let raw = RawMerkleProof::try_from(proof.clone()).map_err(|e| {
+        Error::IbcDataError(format!(
+            "Decoding the proof of the {name} failed: {e}"
+        ))
+    })?;
+    if raw.proofs.is_empty() || raw.proofs.iter().any(|p| p.proof.is_none()) {
+        return Err(Error::IbcDataError(format!(
+            "The proof of the {name} is empty"
+        )));
+    }
+    validate_proof_structure(name, &raw)?;
+    Ok(raw.into())
+}
+
+/// Check the structure of the ICS-23 commitment proofs, so that a proof in a
+/// wrong format is rejected before the client verifies it
+fn validate_proof_structure(
+    name: &str,
+    proof: &RawMerkleProof,
+) -> VpResult<()> {
+    let is_well_formed = proof.proofs.iter().all(|p| match &p.proof {
+        Some(Ics23Proof::Exist(exist)) => !exist.key.is_empty(),
+        Some(Ics23Proof::Nonexist(nonexist)) => {
+            nonexist.left.is_some() || nonexist.right.is_some()
+        }
+        Some(Ics23Proof::Batch(batch)) => !batch.entries.is_empty(),
+        Some(Ics23Proof::Compressed(compressed)) => {
+            !compressed.entries.is_empty()
+        }
+        None => false,
+    });
+    if !is_well_formed {
+        return Err(Error::ProofVerificationError(format!(
+            "The proof structure is malformed: The proof of the {name}"
+        )));
+    }
+    Ok(())
+}
+
+/// Check that the proof is constructed against the upgrade path. With an
+/// upgrade path of a store and a key, the outermost proof of the Merkle proof
+/// proves the store, so its key has to be the store of the upgrade path.
+fn validate_proof_upgrade_path(
+    client_id: &ClientId,
+    proof: &MerkleProof,
+    upgrade_path: &[String],
+) -> VpResult<()> {
+    let store = match upgrade_path {
+        [store, _] => store,
+        // The whole path is proven by a single proof
+        _ => return Ok(()),
+    };
+    let outermost = proof.proofs.last().and_then(|p| p.proof.as_ref());
+    if let Some(Ics23Proof::Exist(exist)) = outermost {
+        if exist.key != store.as_bytes() {
+            return Err(Error::ProofVerificationError(format!(
+                "The upgrade path is wrong: The proof isn't constructed \
+                 against the upgrade path {}: ID {client_id}, Proven store {}",
+                upgrade_path.join("/"),
+                String::from_utf8_lossy(&exist.key)
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check that the version of the upgraded client state, declared in its
+/// type URL, is supported
+fn validate_upgraded_client_version(msg: &MsgUpgradeClient) -> VpResult<()> {
+    let type_url = msg.upgraded_client_state.type_url.as_str();
+    if !SUPPORTED_CLIENT_STATE_TYPE_URLS.contains(&type_url) {
+        return Err(Error::UnsupportedVersion(format!(
+            "The version of the upgraded client state isn't supported: ID {}, \
+             Type URL {type_url}",
+            msg.client_id
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the upgrade path configured in the client state, if the client
+/// type has one
+fn upgrade_path(client_state: &AnyClientState) -> Option<Vec<String>> {
+    match client_state {
+        AnyClientState::Tendermint(cs) => Some(cs.inner().upgrade_path.clone()),
+        // The mock client state for testing
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// Check that the type URL of the stored client state isn't changed. The
+/// type URLs are compared as stored, because the client type of a decoded
+/// client state is derived from its variant. A type URL which differs only
+/// in the case can't be decoded, so it's rejected before this check.
+fn validate_client_type_unchanged(
+    prior: &str,
+    posterior: &str,
+) -> VpResult<()> {
+    if prior != posterior {
+        return Err(Error::ClientError(format!(
+            "The client type was changed: Prior {prior}, Posterior {posterior}"
+        )));
+    }
+    Ok(())
+}
+
+/// Check that the proof specs of a Tendermint client aren't changed. The
+/// check is a no-op for client types without proof specs.
+fn validate_proof_specs_unchanged(
+    prior: &AnyClientState,
+    posterior: &AnyClientState,
+) -> VpResult<()> {
+    match (prior, posterior) {
+        (
+            AnyClientState::Tendermint(prior),
+            AnyClientState::Tendermint(posterior),
+        ) if prior.inner().proof_specs != posterior.inner().proof_specs => {
+            Err(Error::ClientError(
+                "The proof specs were changed by the update".to_string(),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check that the next validators hash of a Tendermint consensus state
+/// isn't empty
+fn validate_next_validators_hash(
+    consensus_state: &AnyConsensusState,
+) -> VpResult<()> {
+    match consensus_state {
+        AnyConsensusState::Tendermint(cs)
+            if cs.inner().next_validators_hash.is_empty() =>
+        {
+            Err(Error::HeaderError(
+                "The validators hash is empty".to_string(),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check that the commitment root of the consensus state at the latest height
+/// isn't all zero bytes, which no state can be proven against. Only the
+/// Tendermint roots are checked, the mock client has a placeholder root.
+fn validate_nonzero_root(consensus_state: &AnyConsensusState) -> VpResult<()> {
+    match consensus_state {
+        AnyConsensusState::Tendermint(cs)
+            if cs.inner().root.as_bytes().iter().all(|byte| *byte == 0) =>
+        {
+            Err(Error::ClientError(
+                "The commitment root at the latest height is zero".to_string(),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check that the timestamp of the consensus state is after the Unix epoch.
+/// A timestamp at the epoch is a zero or unset timestamp.
+fn validate_nonzero_timestamp(
+    consensus_state: &AnyConsensusState,
+) -> VpResult<()> {
+    if consensus_state.timestamp().nanoseconds() == 0 {
+        return Err(Error::ClientError("The timestamp is zero".to_string()));
+    }
+    Ok(())
+}
+
+/// Make the client ID of the client created with the given counter
+fn new_client_id(client_type: ClientType, counter: u64) -> VpResult<ClientId> {
+    ClientId::new(client_type, counter).map_err(|e| {
+        Error::ClientError(format!(
+            "Creating the client ID failed: Counter {counter}, Error {e}"
+        ))
+    })
+}
+
+/// Parse the height of the consensus state key
+fn parse_consensus_height(key: &Key) -> VpResult<Height> {
+    consensus_height(key).map_err(|e| Error::KeyError {
+        key: key.to_string(),
+        reason: format!("Parsing the consensus height failed: {e}"),
+    })
+}
+
+/// Decode the stored client counter, checking that it's a sane value in the
+/// expected format
+fn decode_client_counter(bytes: Option<Vec<u8>>) -> VpResult<u64> {
+    let bytes = bytes.ok_or_else(|| {
+        Error::ClientError("The client counter doesn't exist".to_string())
+    })?;
+    let counter = u64::try_from_slice(&bytes).map_err(|e| {
+        Error::ClientError(format!("Decoding the client counter failed: {e}"))
+    })?;
+    if counter.serialize_to_vec() != bytes {
+        return Err(Error::ClientError(format!(
+            "The client counter isn't encoded as expected: Counter {counter}"
+        )));
+    }
+    Ok(counter)
+}
+
+/// Decode the client state stored with the given key
+fn decode_client_state(key: &Key, bytes: &[u8]) -> VpResult<AnyClientState> {
+    decode_client_state_any(key, bytes)
+        .and_then(|any| client_state_from_any(key, any))
+}
+
+/// Decode the `Any` of the client state stored with the given key, keeping
+/// the type URL the client state was stored with
+fn decode_client_state_any(key: &Key, bytes: &[u8]) -> VpResult<Any> {
+    #[cfg(test)]
+    count_decoded_state();
+    Any::decode(bytes).map_err(|e| {
+        Error::ClientError(format!(
+            "Decoding the client state failed: Key {key}, Error {e}"
+        ))
+    })
+}
+
+/// Convert the decoded `Any` of the client state stored with the given key
+fn client_state_from_any(key: &Key, any: Any) -> VpResult<AnyClientState> {
+    AnyClientState::try_from(any).map_err(|e| {
+        Error::ClientError(format!(
+            "Decoding the client state failed: Key {key}, Error {e}"
+        ))
+    })
+}
+
+/// Decode the consensus state stored with the given key. The bytes which
+/// can't be decoded are reported distinctly from an absent consensus state.
+fn decode_consensus_state(
+    key: &Key,
+    bytes: Vec<u8>,
+) -> VpResult<AnyConsensusState> {
+    #[cfg(test)]
+    count_decoded_state();
+    AnyConsensusState::try_from(bytes).map_err(|e| {
+        Error::ClientError(format!(
+            "The consensus state is present but undecodable: Key {key}, Error \
+             {e}"
+        ))
+    })
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of the client and consensus states decoded on this thread,
+    /// to test that a validation decodes each state once
+    static DECODED_STATES: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
+/// Count a decoded client or consensus state
+#[cfg(test)]
+fn count_decoded_state() {
+    DECODED_STATES.with(|count| count.set(count.get() + 1));
+}
+
+#[cfg(any(test, feature = "testing"))]
+/// Testing helpers for the IBC client validation
+pub mod testing {
+    use super::*;
+    use crate::ibc::clients::tendermint::client_state::ClientState as TmClientState;
+    use crate::ibc::clients::tendermint::types::{
+        AllowUpdate, ClientState as TmClientStateType, TrustThreshold,
+    };
+    use crate::ledger::storage::ics23_specs::ibc_proof_specs;
+    use crate::ledger::storage::traits::Sha256Hasher;
+
+    /// The client types known by the mock reader, in their canonical form
+    const KNOWN_CLIENT_TYPES: [&str; 2] = ["07-tendermint", "9999-mock"];
+
+    /// Parse a raw client type string after normalizing it, i.e. trimming
+    /// the surrounding whitespace and matching a known client type
+    /// regardless of the case
+    fn parse_client_type(raw: &str) -> VpResult<ClientType> {
+        let trimmed = raw.trim();
+        let normalized = KNOWN_CLIENT_TYPES
+            .into_iter()
+            .find(|known| known.eq_ignore_ascii_case(trimmed))
+            .unwrap_or(trimmed);
+        ClientType::new(normalized).map_err(|e| {
+            Error::ClientError(format!(
+                "Parsing the client type failed: Raw {raw:?}, Error {e}"
+            ))
+        })
+    }
+
+    /// A [`ClientReader`] backed by in-memory maps instead of the storage
+    ///
+    /// The example needs the `testing` feature and the mock clients of
+    /// `ibc-testkit`, so it isn't compiled as a doctest.
+    ///
+    /// ```ignore
+    /// use ibc_testkit::testapp::ibc::clients::mock::client_state::{
+    ///     client_type, MockClientState,
+    /// };
+    /// use ibc_testkit::testapp::ibc::clients::mock::consensus_state::MockConsensusState;
+    /// use ibc_testkit::testapp::ibc::clients::mock::header::MockHeader;
+    /// use namada::ibc::core::client::types::Height;
+    /// use namada::ibc::core::host::types::identifiers::ClientId;
+    /// use namada::ibc::primitives::Timestamp;
+    /// use namada::ledger::native_vp::ibc::client::testing::MockClientReader;
+    /// use namada::ledger::native_vp::ibc::client::ClientReader;
+    ///
+    /// let client_id = ClientId::new(client_type(), 0).unwrap();
+    /// let height = Height::new(0, 1).unwrap();
+    /// let header = MockHeader {
+    ///     height,
+    ///     timestamp: Timestamp::now(),
+    /// };
+    ///
+    /// let mut reader = MockClientReader::default();
+    /// reader.insert_client_state(
+    ///     client_id.clone(),
+    ///     MockClientState::new(header).into(),
+    /// );
+    /// reader.insert_consensus_state(
+    ///     client_id.clone(),
+    ///     height,
+    ///     MockConsensusState::new(header).into(),
+    /// );
+    /// reader.set_client_counter(1);
+    ///
+    /// assert!(reader.consensus_state(&client_id, height).unwrap().is_some());
+    /// assert_eq!(reader.client_type(&client_id).unwrap(), Some(client_type()));
+    /// ```
+    #[derive(Debug, Default)]
+    pub struct MockClientReader {
+        /// Raw client types, parsed when they are read
+        pub client_types: HashMap<ClientId, String>,
+        /// Client states
+        pub client_states: HashMap<ClientId, AnyClientState>,
+        /// Encoded consensus states
+        pub consensus_states: HashMap<(ClientId, Height), Vec<u8>>,
+        /// Client counter
+        pub client_counter: u64,
+    }
+
+    impl MockClientReader {
+        /// Insert a client state with its client type
+        pub fn insert_client_state(
+            &mut self,
+            client_id: ClientId,
+            client_state: AnyClientState,
+        ) {
+            self.client_types.insert(
+                client_id.clone(),
+                client_state.client_type().as_str().to_string(),
+            );
+            self.client_states.insert(client_id, client_state);
+        }
+
+        /// Set the raw client type of the client
+        pub fn insert_client_type(
+            &mut self,
+            client_id: ClientId,
+            client_type: impl Into<String>,
+        ) {
+            self.client_types.insert(client_id, client_type.into());
+        }
+
+        /// Insert a consensus state at the given height
+        pub fn insert_consensus_state(
+            &mut self,
+            client_id: ClientId,
+            height: Height,
+            consensus_state: AnyConsensusState,
+        ) {
+            self.consensus_states
+                .insert((client_id, height), consensus_state.encode_vec());
+        }
+
+        /// Set the client counter
+        pub fn set_client_counter(&mut self, counter: u64) {
+            self.client_counter = counter;
+        }
+    }
+
+    impl ClientReader for MockClientReader {
+        fn client_state(
+            &self,
+            client_id: &ClientId,
+        ) -> VpResult<Option<AnyClientState>> {
+            Ok(self.client_states.get(client_id).cloned())
+        }
+
+        fn consensus_state(
+            &self,
+            client_id: &ClientId,
+            height: Height,
+        ) -> VpResult<Option<AnyConsensusState>> {
+            let key = consensus_state_key(client_id, height);
+            self.consensus_states
+                .get(&(client_id.clone(), height))
+                .map(|bytes| decode_consensus_state(&key, bytes.clone()))
+                .transpose()
+        }
+
+        fn client_counter(&self) -> VpResult<u64> {
+            Ok(self.client_counter)
+        }
+
+        fn client_type(
+            &self,
+            client_id: &ClientId,
+        ) -> VpResult<Option<ClientType>> {
+            self.client_types
+                .get(client_id)
+                .map(|raw| parse_client_type(raw))
+                .transpose()
+        }
+    }
+
+    /// Build a valid Tendermint client state for tests, with the default
+    /// upgrade path, the proof specs of Namada, an unbonding period of 1.5
+    /// times the trusting period and a max clock drift of 60 seconds
+    pub fn test_tendermint_client_state(
+        chain_id: ChainId,
+        height: Height,
+        trusting_period: Duration,
+    ) -> AnyClientState {
+        TmClientStateType::new(
+            chain_id,
+            TrustThreshold::default(),
+            trusting_period,
+            trusting_period * 3 / 2,
+            Duration::from_secs(60),
+            height,
+            ibc_proof_specs::<Sha256Hasher>().into(),
+            vec!["upgrade".to_string(), "upgradedIBCState".to_string()],
+            AllowUpdate {
+                after_expiry: true,
+                after_misbehaviour: true,
+            },
+        )
+        .map(TmClientState::from)
+        .expect("the test client state should be valid")
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::collections::BTreeSet;
+    use std::env::temp_dir;
+    use std::str::FromStr;
+
+    use ibc_testkit::testapp::ibc::clients::mock::client_state::{
+        client_type, MockClientState,
+    };
+    use ibc_testkit::testapp::ibc::clients::mock::consensus_state::MockConsensusState;
+    use ibc_testkit::testapp::ibc::clients::mock::header::MockHeader;
+    use namada_core::ledger::gas::TxGasMeter;
+
+    use super::testing::{test_tendermint_client_state, MockClientReader};
+    use super::*;
+    use crate::core::ledger::storage::testing::TestWlStorage;
+    use crate::ibc::clients::tendermint::client_state::ClientState as TmClientState;
+    use crate::ibc::clients::tendermint::consensus_state::ConsensusState as TmConsensusState;
+    use crate::ibc::clients::tendermint::types::ClientState as TmClientStateType;
+    use crate::ibc::core::client::types::Height;
+    use crate::ibc::core::host::types::path::{
+        ClientStatePath, UpgradeClientPath,
+    };
+    use crate::ibc::primitives::proto::Protobuf;
+    use crate::ibc::primitives::Msg;
+    use crate::ledger::gas::VpGasMeter;
+    use crate::ledger::ibc;
+    use crate::ledger::native_vp::Ctx;
+    use crate::ledger::storage::ics23_specs::ibc_proof_specs;
+    use crate::ledger::storage::merkle_tree::{MerkleRoot, MerkleTree};
+    use crate::ledger::storage::mockdb::MockDB;
+    use crate::ledger::storage::traits::Sha256Hasher;
+    use crate::proto::Tx;
+    use crate::tendermint::block::header::Version;
+    use crate::tendermint::block::signed_header::SignedHeader;
+    use crate::tendermint::block::{
+        self, Commit, Header as TmBlockHeader, Height as TmHeight, Round,
+    };
+    use crate::tendermint::validator::Set as ValidatorSet;
+    use crate::tendermint::{account, validator, vote, AppHash};
+    use crate::types::address::{Address, InternalAddress};
+    use crate::types::hash::Hash;
+    use crate::types::key::testing::keypair_1;
+    use crate::types::key::RefTo;
+    use crate::types::storage::{Header, MembershipProof, TxIndex};
+    use crate::types::transaction::TxType;
+    use crate::vm::wasm::VpCache;
+    use crate::vm::WasmCacheRwAccess;
+
+    const ADDRESS: Address = Address::Internal(InternalAddress::Ibc);
+
+    /// Setup a ctx for running the IBC VP
+    fn setup_ctx<'a>(
+        tx: &'a Tx,
+        wl_storage: &'a TestWlStorage,
+        keys_changed: &'a BTreeSet<Key>,
+        verifiers: &'a BTreeSet<Address>,
+    ) -> Ctx<'a, MockDB, Sha256Hasher, WasmCacheRwAccess> {
+        Ctx::new(
+            &ADDRESS,
+            &wl_storage.storage,
+            &wl_storage.write_log,
+            tx,
+            &TxIndex(0),
+            VpGasMeter::new_from_tx_meter(&TxGasMeter::new_from_sub_limit(
+                u64::MAX.into(),
+            )),
+            keys_changed,
+            verifiers,
+            VpCache::new(temp_dir(), 100usize),
+        )
+    }
+
+    fn init_storage() -> TestWlStorage {
+        let mut wl_storage = TestWlStorage::default();
+        ibc::init_genesis_storage(&mut wl_storage);
+        wl_storage.commit_block().expect("commit failed");
+        wl_storage
+    }
+
+    fn get_client_id(counter: u64) -> ClientId {
+        ClientId::new(client_type(), counter).expect("invalid client ID")
+    }
+
+    /// Write a mock client state to the write log
+    fn write_client_state(
+        wl_storage: &mut TestWlStorage,
+        client_id: &ClientId,
+        client_state: MockClientState,
+    ) -> Key {
+        let key = client_state_key(client_id);
+        let bytes = Protobuf::<Any>::encode_vec(client_state);
+        wl_storage
+            .write_log
+            .write(&key, bytes)
+            .expect("write failed");
+        key
+    }
+
+    fn mock_client_state(height: u64) -> MockClientState {
+        let header = MockHeader {
+            height: Height::new(0, height).unwrap(),
+            timestamp: Timestamp::now(),
+        };
+        MockClientState::new(header)
+    }
+
+    #[test]
+    fn test_counter_consistency() {
+        let mut wl_storage = init_storage();
+        let mut keys_changed = BTreeSet::new();
+        for counter in 0..2 {
+            let key = write_client_state(
+                &mut wl_storage,
+                &get_client_id(counter),
+                mock_client_state(1),
+            );
+            keys_changed.insert(key);
+        }
+        // a deliberately low counter
+        let counter_key = client_counter_key();
+        wl_storage
+            .write_log
+            .write(&counter_key, 1u64.serialize_to_vec())
+            .expect("write failed");
+        keys_changed.insert(counter_key.clone());
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            assert_eq!(ibc.iter_client_ids().unwrap().len(), 2);
+            let result = ibc.validate_counter_consistency().unwrap_err();
+            assert!(matches!(result, Error::ClientError(_)));
+        }
+
+        wl_storage
+            .write_log
+            .write(&counter_key, 2u64.serialize_to_vec())
+            .expect("write failed");
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.validate_counter_consistency()
+            .expect("the counter should be consistent");
+    }
+
+    #[test]
+    fn test_parse_client_type() {
+        let client_id = get_client_id(0);
+        let mut reader = MockClientReader::default();
+        reader.insert_client_type(client_id.clone(), " 07-Tendermint \n");
+        assert_eq!(
+            reader.client_type(&client_id).unwrap(),
+            Some(ClientType::new("07-tendermint").unwrap())
+        );
+
+        reader.insert_client_type(client_id.clone(), "  bad type ");
+        let result = reader.client_type(&client_id).unwrap_err();
+        match &result {
+            Error::ClientError(msg) => assert!(msg.contains("\"  bad type \"")),
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_client_type_unchanged() {
+        let prior = "/ibc.lightclients.tendermint.v1.ClientState";
+        validate_client_type_unchanged(prior, prior)
+            .expect("the same type URL should be accepted");
+
+        let result =
+            validate_client_type_unchanged(prior, "/ibc.mock.ClientState")
+                .unwrap_err();
+        assert!(matches!(result, Error::ClientError(_)));
+    }
+
+    #[test]
+    fn test_update_rewriting_type_url_case() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        write_client_state(&mut wl_storage, &client_id, mock_client_state(10));
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        // the update stores the client state with a type URL differing only
+        // in the case
+        let client_state = mock_client_state(11);
+        let mut any = Any::from(client_state);
+        any.type_url = any.type_url.replace("mock", "Mock");
+        let key = client_state_key(&client_id);
+        wl_storage
+            .write_log
+            .write(&key, any.encode_to_vec())
+            .expect("write failed");
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(key);
+        let msg = MsgUpdateClient {
+            client_id,
+            client_message: client_state.header.into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx_data = msg.to_any().encode_to_vec();
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc.validate_client(&tx_data).unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert!(msg.starts_with("Decoding the client state failed"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_consensus_state_root() {
+        let mut wl_storage = init_storage();
+        let client_id =
+            ClientId::new(ClientType::new("07-tendermint").unwrap(), 0)
+                .unwrap();
+        let height = Height::new(0, 10).unwrap();
+        let root = CommitmentRoot::from_bytes(&[1; 32]);
+        // the stored root has been tampered
+        let tampered: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&[2; 32]),
+            TmTime::now(),
+            TmHash::None,
+        )
+        .into();
+        let consensus_key = consensus_state_key(&client_id, height);
+        wl_storage
+            .write_log
+            .write(
+                &consensus_key,
+                AnyConsensusState::from(tampered).encode_vec(),
+            )
+            .expect("write failed");
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(consensus_key.clone());
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let consensus_state =
+                ibc.consensus_state_post(&client_id, height).unwrap();
+            let result = validate_consensus_state_root(
+                consensus_state.as_ref(),
+                &client_id,
+                height,
+                &root,
+            )
+            .unwrap_err();
+            match &result {
+                Error::HeaderError(msg) => {
+                    assert!(msg.contains(
+                        "The consensus root doesn't match the header"
+                    ))
+                }
+                _ => panic!("unexpected error: {result}"),
+            }
+        }
+
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            root.clone(),
+            TmTime::now(),
+            TmHash::None,
+        )
+        .into();
+        wl_storage
+            .write_log
+            .write(
+                &consensus_key,
+                AnyConsensusState::from(consensus_state).encode_vec(),
+            )
+            .expect("write failed");
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let consensus_state =
+            ibc.consensus_state_post(&client_id, height).unwrap();
+        validate_consensus_state_root(
+            consensus_state.as_ref(),
+            &client_id,
+            height,
+            &root,
+        )
+        .expect("the root should match");
+    }
+
+    #[test]
+    fn test_frozen_client_ids() {
+        let mut wl_storage = init_storage();
+        let mut keys_changed = BTreeSet::new();
+        let frozen_client_id = get_client_id(0);
+        let client_state = mock_client_state(10)
+            .with_frozen_height(Height::new(0, 10).unwrap());
+        let key = write_client_state(
+            &mut wl_storage,
+            &frozen_client_id,
+            client_state,
+        );
+        keys_changed.insert(key);
+        for counter in 1..3 {
+            let key = write_client_state(
+                &mut wl_storage,
+                &get_client_id(counter),
+                mock_client_state(10),
+            );
+            keys_changed.insert(key);
+        }
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        assert_eq!(ibc.iter_client_ids().unwrap().len(), 3);
+        assert_eq!(ibc.frozen_client_ids().unwrap(), vec![frozen_client_id]);
+    }
+
+    #[test]
+    fn test_create_existing_client() {
+        let mut wl_storage = init_storage();
+        // the client to be created already exists in the prior state
+        let client_state = mock_client_state(10);
+        write_client_state(&mut wl_storage, &get_client_id(0), client_state);
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        let msg = MsgCreateClient {
+            client_state: client_state.into(),
+            consensus_state: MockConsensusState::new(client_state.header)
+                .into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_create_client(&msg, None, &mut ClientChecks::fail_fast())
+            .unwrap_err();
+        assert!(matches!(result, Error::StateChange(_)));
+    }
+
+    #[test]
+    fn test_empty_next_validators_hash() {
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&[1; 32]),
+            TmTime::now(),
+            TmHash::None,
+        )
+        .into();
+        let result =
+            validate_next_validators_hash(&consensus_state.into()).unwrap_err();
+        assert!(matches!(result, Error::HeaderError(_)));
+
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&[1; 32]),
+            TmTime::now(),
+            TmHash::Sha256([2; 32]),
+        )
+        .into();
+        validate_next_validators_hash(&consensus_state.into())
+            .expect("the hash should be non-empty");
+    }
+
+    #[test]
+    fn test_decode_updated_client_once() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        write_client_state(&mut wl_storage, &client_id, mock_client_state(10));
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+        let key = write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(11),
+        );
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(key);
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let updated = {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.decode_updated_client(&client_id, []).unwrap()
+        };
+        assert_eq!(updated.prior.latest_height(), Height::new(0, 10).unwrap());
+        assert_eq!(
+            updated.posterior.latest_height(),
+            Height::new(0, 11).unwrap()
+        );
+        assert!(updated.consensus_state().is_none());
+
+        // the checks only use the decoded states, the context has already
+        // been dropped
+        updated_client_checks(&client_id, &updated)
+            .into_iter()
+            .collect::<VpResult<()>>()
+            .expect("the updated client should be valid");
+    }
+
+    #[test]
+    fn test_update_decodes_states_once() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let now = Timestamp::now();
+        let mock_consensus_state = |height: u64| {
+            let header = MockHeader {
+                height: Height::new(0, height).unwrap(),
+                timestamp: (now + Duration::from_secs(height)).unwrap(),
+            };
+            Protobuf::<Any>::encode_vec(MockConsensusState::new(header))
+        };
+        write_client_state(&mut wl_storage, &client_id, mock_client_state(10));
+        for height in 1..=10 {
+            wl_storage
+                .write_log
+                .write(
+                    &consensus_state_key(
+                        &client_id,
+                        Height::new(0, height).unwrap(),
+                    ),
+                    mock_consensus_state(height),
+                )
+                .expect("write failed");
+        }
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        // the update adds the consensus states of two headers
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(12),
+        ));
+        for height in 11..=12 {
+            let key = consensus_state_key(
+                &client_id,
+                Height::new(0, height).unwrap(),
+            );
+            wl_storage
+                .write_log
+                .write(&key, mock_consensus_state(height))
+                .expect("write failed");
+            keys_changed.insert(key);
+        }
+        let msg = MsgUpdateClient {
+            client_id: client_id.clone(),
+            client_message: MockHeader {
+                height: Height::new(0, 12).unwrap(),
+                timestamp: (now + Duration::from_secs(12)).unwrap(),
+            }
+            .into(),
+            signer: "account0".to_string().into(),
+        };
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        DECODED_STATES.with(|count| count.set(0));
+        ibc.verify_update_client(&msg, false, &mut ClientChecks::fail_fast())
+            .expect("the update should be valid");
+        // the prior and the posterior client states, and the posterior
+        // consensus states at the prior latest height and the two added
+        // heights, without the older history
+        assert_eq!(DECODED_STATES.with(|count| count.get()), 5);
+    }
+
+    #[test]
+    fn test_consensus_state_bytes() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let height = Height::new(0, 10).unwrap();
+        let header = MockHeader {
+            height,
+            timestamp: Timestamp::now(),
+        };
+        wl_storage
+            .write_log
+            .write(
+                &consensus_state_key(&client_id, height),
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(header)),
+            )
+            .expect("write failed");
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        assert!(ibc
+            .consensus_state_bytes(&client_id, height, false)
+            .unwrap()
+            .is_none());
+        let bytes = ibc
+            .consensus_state_bytes(&client_id, height, true)
+            .unwrap()
+            .expect("the consensus state should exist");
+        let consensus_state = AnyConsensusState::try_from(bytes)
+            .expect("the bytes should be decoded");
+        assert_eq!(consensus_state.timestamp(), header.timestamp);
+    }
+
+    #[test]
+    fn test_client_action() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(10),
+        ));
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            assert_eq!(
+                ibc.client_action(&client_id, &[]).unwrap(),
+                ClientAction::Created
+            );
+        }
+
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+        keys_changed.clear();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            assert_eq!(
+                ibc.client_action(&client_id, &[]).unwrap(),
+                ClientAction::Unchanged
+            );
+        }
+
+        keys_changed.insert(write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(11),
+        ));
+        let update = MsgUpdateClient {
+            client_id: client_id.clone(),
+            client_message: mock_client_state(11).header.into(),
+            signer: "account0".to_string().into(),
+        };
+        let mut tx_data = vec![];
+        update
+            .to_any()
+            .encode(&mut tx_data)
+            .expect("encoding failed");
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        assert_eq!(
+            ibc.client_action(&client_id, &tx_data).unwrap(),
+            ClientAction::Updated
+        );
+
+        let upgrade = MsgUpgradeClient {
+            client_id: client_id.clone(),
+            upgraded_client_state: mock_client_state(11).into(),
+            upgraded_consensus_state: MockConsensusState::new(
+                mock_client_state(11).header,
+            )
+            .into(),
+            proof_upgrade_client: dummy_proof(),
+            proof_upgrade_consensus_state: dummy_proof(),
+            signer: "account0".to_string().into(),
+        };
+        let mut tx_data = vec![];
+        upgrade
+            .to_any()
+            .encode(&mut tx_data)
+            .expect("encoding failed");
+        assert_eq!(
+            ibc.client_action(&client_id, &tx_data).unwrap(),
+            ClientAction::Upgraded
+        );
+    }
+
+    #[test]
+    fn test_validate_client_verbose() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        write_client_state(&mut wl_storage, &client_id, mock_client_state(10));
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+        let mut keys_changed = BTreeSet::new();
+        let key = write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(11),
+        );
+        keys_changed.insert(key);
+        // the consensus state has an empty validators hash
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&[1; 32]),
+            TmTime::now(),
+            TmHash::None,
+        )
+        .into();
+        let consensus_key =
+            consensus_state_key(&client_id, Height::new(0, 11).unwrap());
+        wl_storage
+            .write_log
+            .write(
+                &consensus_key,
+                AnyConsensusState::from(consensus_state).encode_vec(),
+            )
+            .expect("write failed");
+        keys_changed.insert(consensus_key);
+        // the client counter is incremented by the update
+        wl_storage
+            .write_log
+            .write(&client_counter_key(), 1u64.serialize_to_vec())
+            .expect("write failed");
+
+        let header = MockHeader {
+            height: Height::new(0, 11).unwrap(),
+            timestamp: Timestamp::now(),
+        };
+        let msg = MsgUpdateClient {
+            client_id: client_id.clone(),
+            client_message: header.into(),
+            signer: "account0".to_string().into(),
+        };
+        let mut tx_data = vec![];
+        msg.to_any().encode(&mut tx_data).expect("encoding failed");
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        assert!(ibc.validate_client(&tx_data).is_err());
+        let (is_valid, errors) =
+            ibc.validate_client_verbose(&tx_data, &Default::default());
+        assert!(!is_valid);
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| matches!(e, Error::ClientError(_))));
+        assert!(errors.iter().any(|e| matches!(e, Error::HeaderError(_))));
+
+        #[derive(Default)]
+        struct CountingObserver {
+            updated: Cell<usize>,
+            errors: Cell<usize>,
+        }
+
+        impl ValidationObserver for CountingObserver {
+            fn on_updated(&self, _msg: &MsgUpdateClient) {
+                self.updated.set(self.updated.get() + 1);
+            }
+
+            fn on_error(&self, _error: &Error) {
+                self.errors.set(self.errors.get() + 1);
+            }
+        }
+
+        let observer = CountingObserver::default();
+        assert!(ibc
+            .validate_client_with_observer(&tx_data, &observer)
+            .is_err());
+        assert_eq!(observer.updated.get(), 0);
+        assert_eq!(observer.errors.get(), 1);
+    }
+
+    fn tm_client_state(upgrade_path: Vec<String>) -> TmClientState {
+        let AnyClientState::Tendermint(client_state) =
+            test_tendermint_client_state(
+                ChainId::from_str("test-chain-0").unwrap(),
+                Height::new(0, 10).unwrap(),
+                Duration::from_secs(1000),
+            )
+        else {
+            unreachable!("the test client state should be Tendermint");
+        };
+        let mut client_state = client_state.inner().clone();
+        client_state.upgrade_path = upgrade_path;
+        client_state.into()
+    }
+
+    /// The hash of the empty validator set signing the test headers
+    fn empty_validators_hash() -> TmHash {
+        ValidatorSet::new(vec![], None).hash()
+    }
+
+    /// A validator set with one validator, other than the empty one signing
+    /// the test headers
+    fn other_validator_set() -> ValidatorSet {
+        let validator = validator::Info::new(
+            keypair_1().ref_to().into(),
+            vote::Power::from(1_u32),
+        );
+        ValidatorSet::new(vec![validator], None)
+    }
+
+    /// A Tendermint header of the given chain at the given height, trusting
+    /// the given height of the same revision. The header has the app hash
+    /// `[1; 32]` and is signed by the empty validator set.
+    fn tm_header(
+        chain_id: &str,
+        height: u64,
+        trusted_height: u64,
+        time: TmTime,
+    ) -> TmHeader {
+        let validator_set = ValidatorSet::new(vec![], None);
+        let height = TmHeight::try_from(height).unwrap();
+        let header = TmBlockHeader {
+            version: Version { block: 11, app: 0 },
+            chain_id: chain_id.parse().unwrap(),
+            height,
+            time,
+            last_block_id: None,
+            last_commit_hash: None,
+            data_hash: None,
+            validators_hash: validator_set.hash(),
+            next_validators_hash: validator_set.hash(),
+            consensus_hash: TmHash::None,
+            app_hash: AppHash::try_from(vec![1; 32]).unwrap(),
+            last_results_hash: None,
+            evidence_hash: None,
+            proposer_address: account::Id::new([0; 20]),
+        };
+        let commit = Commit {
+            height,
+            round: Round::default(),
+            block_id: block::Id::default(),
+            signatures: vec![],
+        };
+        let trusted_height = Height::new(
+            ChainId::from_str(chain_id).unwrap().revision_number(),
+            trusted_height,
+        )
+        .unwrap();
+        TmHeader {
+            signed_header: SignedHeader::new(header, commit).unwrap(),
+            validator_set: validator_set.clone(),
+            trusted_height,
+            trusted_next_validator_set: validator_set,
+        }
+    }
+
+    /// Write a Tendermint consensus state of the client with the given root
+    /// to the write log. Its next validator set is the empty one.
+    fn write_tm_consensus_state(
+        wl_storage: &mut TestWlStorage,
+        client_id: &ClientId,
+        height: Height,
+        root: [u8; 32],
+        time: TmTime,
+    ) -> Key {
+        let key = consensus_state_key(client_id, height);
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&root),
+            time,
+            empty_validators_hash(),
+        )
+        .into();
+        wl_storage
+            .write_log
+            .write(&key, AnyConsensusState::from(consensus_state).encode_vec())
+            .expect("write failed");
+        key
+    }
+
+    /// Set the time of the current block
+    fn set_block_time(wl_storage: &mut TestWlStorage, time: TmTime) {
+        wl_storage
+            .storage
+            .set_header(Header {
+                hash: Hash([0; 32]),
+                time: time.try_into().unwrap(),
+                next_validators_hash: Hash([0; 32]),
+            })
+            .expect("Setting a header shouldn't fail");
+    }
+
+    /// Initialize a Tendermint client of the test chain at the height 10
+    /// with the consensus states at the heights 5, 9 and 10 with the root
+    /// `[2; 32]`, and set the block time to the given time. The consensus
+    /// states are 60, 20 and 10 seconds older than the block.
+    fn init_tm_client(wl_storage: &mut TestWlStorage, now: TmTime) -> ClientId {
+        let client_id =
+            ClientId::new(ClientType::new("07-tendermint").unwrap(), 0)
+                .unwrap();
+        wl_storage
+            .write_log
+            .write(
+                &client_state_key(&client_id),
+                Protobuf::<Any>::encode_vec(tm_client_state(vec![])),
+            )
+            .expect("write failed");
+        for height in [5, 9, 10] {
+            let time = (now - Duration::from_secs(10 * (11 - height))).unwrap();
+            write_tm_consensus_state(
+                wl_storage,
+                &client_id,
+                Height::new(0, height).unwrap(),
+                [2; 32],
+                time,
+            );
+        }
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+        set_block_time(wl_storage, now);
+        client_id
+    }
+
+    /// Write the update of the Tendermint client with the given header to the
+    /// write log like the IBC handler, i.e. the consensus state derived from
+    /// the header and the client state advanced to the header height if it's
+    /// higher, and returns the changed keys
+    fn write_tm_update(
+        wl_storage: &mut TestWlStorage,
+        client_id: &ClientId,
+        header: &TmHeader,
+    ) -> BTreeSet<Key> {
+        let mut keys_changed = BTreeSet::new();
+        let mut client_state = tm_client_state(vec![]).inner().clone();
+        if header.height() > client_state.latest_height {
+            client_state.latest_height = header.height();
+            keys_changed.insert(write_tm_client_state(
+                wl_storage,
+                client_id,
+                client_state,
+            ));
+        }
+        let key = consensus_state_key(client_id, header.height());
+        let consensus_state =
+            TmConsensusState::from(TmConsensusStateType::from(header.clone()));
+        wl_storage
+            .write_log
+            .write(&key, AnyConsensusState::from(consensus_state).encode_vec())
+            .expect("write failed");
+        keys_changed.insert(key);
+        keys_changed
+    }
+
+    /// The transaction data of the update of the client with the given
+    /// Tendermint header
+    fn tm_update_tx_data(client_id: &ClientId, header: TmHeader) -> Vec<u8> {
+        let msg = MsgUpdateClient {
+            client_id: client_id.clone(),
+            client_message: header.into(),
+            signer: "account0".to_string().into(),
+        };
+        msg.to_any().encode_to_vec()
+    }
+
+    /// Write the given Tendermint client state to the write log
+    fn write_tm_client_state(
+        wl_storage: &mut TestWlStorage,
+        client_id: &ClientId,
+        client_state: TmClientStateType,
+    ) -> Key {
+        let key = client_state_key(client_id);
+        wl_storage
+            .write_log
+            .write(
+                &key,
+                Protobuf::<Any>::encode_vec(TmClientState::from(client_state)),
+            )
+            .expect("write failed");
+        key
+    }
+
+    /// A non-empty Merkle proof which doesn't prove anything
+    fn dummy_proof() -> CommitmentProofBytes {
+        // MerkleProof { proofs: [CommitmentProof::Exist { key: [1] }] }
+        CommitmentProofBytes::try_from(vec![
+            0x0a, 0x05, 0x0a, 0x03, 0x0a, 0x01, 0x01,
+        ])
+        .unwrap()
+    }
+
+    /// A proof whose outermost proof is an existence proof of the given key
+    fn proof_with_key(key: &[u8]) -> CommitmentProofBytes {
+        let proof = RawMerkleProof {
+            proofs: vec![ics23::CommitmentProof {
+                proof: Some(Ics23Proof::Exist(ics23::ExistenceProof {
+                    key: key.to_vec(),
+                    ..Default::default()
+                })),
+            }],
+        };
+        CommitmentProofBytes::try_from(proof.encode_to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_upgrade_path() {
+        let mut wl_storage = init_storage();
+        let client_id =
+            ClientId::new(ClientType::new("07-tendermint").unwrap(), 0)
+                .unwrap();
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&[1; 32]),
+            TmTime::now(),
+            TmHash::Sha256([2; 32]),
+        )
+        .into();
+        wl_storage
+            .write_log
+            .write(
+                &consensus_state_key(&client_id, Height::new(0, 10).unwrap()),
+                AnyConsensusState::from(consensus_state).encode_vec(),
+            )
+            .expect("write failed");
+        // the client has no upgrade path
+        let client_state_key = client_state_key(&client_id);
+        wl_storage
+            .write_log
+            .write(
+                &client_state_key,
+                Protobuf::<Any>::encode_vec(tm_client_state(vec![])),
+            )
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        let msg = MsgUpgradeClient {
+            client_id: client_id.clone(),
+            upgraded_client_state: tm_client_state(vec![
+                "upgrade".to_string(),
+                "upgradedIBCState".to_string(),
+            ])
+            .into(),
+            upgraded_consensus_state: TmConsensusState::from(
+                TmConsensusStateType::new(
+                    CommitmentRoot::from_bytes(&[3; 32]),
+                    TmTime::now(),
+                    TmHash::Sha256([4; 32]),
+                ),
+            )
+            .into(),
+            proof_upgrade_client: dummy_proof(),
+            proof_upgrade_consensus_state: dummy_proof(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let assert_wrong_upgrade_path =
+            |wl_storage: &TestWlStorage, msg: &MsgUpgradeClient, is_wrong| {
+                let ctx = setup_ctx(&tx, wl_storage, &keys_changed, &verifiers);
+                let ibc = Ibc { ctx };
+                let result = ibc
+                    .verify_upgrade_client(
+                        msg,
+                        &Default::default(),
+                        &mut ClientChecks::fail_fast(),
+                    )
+                    .unwrap_err();
+                match &result {
+                    Error::ProofVerificationError(msg) => assert_eq!(
+                        msg.starts_with("The upgrade path is wrong"),
+                        is_wrong
+                    ),
+                    _ => panic!("unexpected error: {result}"),
+                }
+            };
+        assert_wrong_upgrade_path(&wl_storage, &msg, true);
+
+        // the proofs aren't constructed against the upgrade path
+        wl_storage
+            .write_log
+            .write(
+                &client_state_key,
+                Protobuf::<Any>::encode_vec(tm_client_state(vec![
+                    "wrong".to_string(),
+                    "path".to_string(),
+                ])),
+            )
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+        assert_wrong_upgrade_path(&wl_storage, &msg, true);
+
+        // the proofs of the store of the upgrade path fail with the error of
+        // the verification
+        wl_storage
+            .write_log
+            .write(
+                &client_state_key,
+                Protobuf::<Any>::encode_vec(tm_client_state(vec![
+                    "upgrade".to_string(),
+                    "upgradedIBCState".to_string(),
+                ])),
+            )
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+        let msg = MsgUpgradeClient {
+            proof_upgrade_client: proof_with_key(b"upgrade"),
+            proof_upgrade_consensus_state: proof_with_key(b"upgrade"),
+            ..msg
+        };
+        assert_wrong_upgrade_path(&wl_storage, &msg, false);
+    }
+
+    /// Prove the given values at the given paths in a Merkle tree of the IBC
+    /// store, and returns the proofs with the root of the tree
+    fn prove_ibc_values(
+        paths_and_values: &[(Path, Vec<u8>)],
+    ) -> (Vec<CommitmentProofBytes>, MerkleRoot) {
+        let mut tree = MerkleTree::<Sha256Hasher>::default();
+        for (path, value) in paths_and_values {
+            tree.update(&ibc_key(path.to_string()).unwrap(), value)
+                .unwrap();
+        }
+        let proofs = paths_and_values
+            .iter()
+            .map(|(path, value)| {
+                let key = ibc_key(path.to_string()).unwrap();
+                let sub_proof = match tree
+                    .get_sub_tree_existence_proof(
+                        std::array::from_ref(&key),
+                        vec![value.as_slice()],
+                    )
+                    .unwrap()
+                {
+                    MembershipProof::ICS23(proof) => proof,
+                    _ => panic!("unexpected proof"),
+                };
+                let proof = tree.get_sub_tree_proof(&key, sub_proof).unwrap();
+                let proof = MerkleProof {
+                    proofs: vec![proof.sub_proof, proof.base_proof],
+                };
+                CommitmentProofBytes::try_from(proof).unwrap()
+            })
+            .collect();
+        (proofs, tree.root())
+    }
+
+    #[test]
+    fn test_upgrade_proofs_prior_root() {
+        let mut wl_storage = init_storage();
+        let client_id =
+            ClientId::new(ClientType::new("07-tendermint").unwrap(), 0)
+                .unwrap();
+        let upgrade_path =
+            vec!["ibc".to_string(), "upgradedIBCState".to_string()];
+        let prior = tm_client_state(upgrade_path.clone());
+        wl_storage
+            .write_log
+            .write(
+                &client_state_key(&client_id),
+                Protobuf::<Any>::encode_vec(prior.clone()),
+            )
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        // the upgraded states are proven in the IBC store of the counterparty
+        // at the latest height of the prior client
+        let upgraded_client_state = Any::from(test_tendermint_client_state(
+            ChainId::from_str("test-chain-1").unwrap(),
+            Height::new(1, 1).unwrap(),
+            Duration::from_secs(1000),
+        ));
+        let upgraded_consensus_state: TmConsensusState =
+            TmConsensusStateType::new(
+                CommitmentRoot::from_bytes(&[3; 32]),
+                TmTime::now(),
+                empty_validators_hash(),
+            )
+            .into();
+        let upgraded_consensus_state = Any::from(upgraded_consensus_state);
+        let (proofs, MerkleRoot(root)) = prove_ibc_values(&[
+            (
+                Path::UpgradeClient(UpgradeClientPath::UpgradedClientState(10)),
+                upgraded_client_state.encode_to_vec(),
+            ),
+            (
+                Path::UpgradeClient(
+                    UpgradeClientPath::UpgradedClientConsensusState(10),
+                ),
+                upgraded_consensus_state.encode_to_vec(),
+            ),
+        ]);
+        let msg = MsgUpgradeClient {
+            client_id: client_id.clone(),
+            upgraded_client_state,
+            upgraded_consensus_state,
+            proof_upgrade_client: proofs[0].clone(),
+            proof_upgrade_consensus_state: proofs[1].clone(),
+            signer: "account0".to_string().into(),
+        };
+        let write_root = |wl_storage: &mut TestWlStorage, root: &[u8]| {
+            let consensus_state: TmConsensusState = TmConsensusStateType::new(
+                CommitmentRoot::from_bytes(root),
+                TmTime::now(),
+                empty_validators_hash(),
+            )
+            .into();
+            let key =
+                consensus_state_key(&client_id, Height::new(0, 10).unwrap());
+            wl_storage
+                .write_log
+                .write(
+                    &key,
+                    AnyConsensusState::from(consensus_state).encode_vec(),
+                )
+                .expect("write failed");
+            key
+        };
+        let prior = AnyClientState::from(prior);
+        let max_proof_size =
+            ClientValidationConfig::default().max_upgrade_proof_size;
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+
+        // only the transaction writes the consensus state with the root
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(write_root(&mut wl_storage, &root));
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let result = ibc
+                .verify_upgrade_proofs(
+                    &msg,
+                    &prior,
+                    &upgrade_path,
+                    max_proof_size,
+                )
+                .unwrap_err();
+            match &result {
+                Error::ProofVerificationError(msg) => {
+                    assert!(msg.starts_with(
+                        "The proofs aren't anchored to a prior root"
+                    ))
+                }
+                _ => panic!("unexpected error: {result}"),
+            }
+        }
+
+        // the proofs are anchored to the posterior root overwriting another
+        // prior root
+        wl_storage.write_log.drop_tx();
+        write_root(&mut wl_storage, &[4; 32]);
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+        write_root(&mut wl_storage, &root);
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let result = ibc
+                .verify_upgrade_proofs(
+                    &msg,
+                    &prior,
+                    &upgrade_path,
+                    max_proof_size,
+                )
+                .unwrap_err();
+            match &result {
+                Error::ProofVerificationError(msg) => {
+                    assert!(
+                        msg.starts_with("Verifying the upgrade proofs failed")
+                    )
+                }
+                _ => panic!("unexpected error: {result}"),
+            }
+        }
+
+        // the proofs are anchored to the prior root
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+        let keys_changed = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.verify_upgrade_proofs(&msg, &prior, &upgrade_path, max_proof_size)
+            .expect("the upgrade proofs should be verified");
+
+        // the upgrade path in the prior client state is another one
+        let result = ibc
+            .verify_upgrade_proofs(
+                &msg,
+                &prior,
+                &["upgrade".to_string(), "upgradedIBCState".to_string()],
+                max_proof_size,
+            )
+            .unwrap_err();
+        match &result {
+            Error::ProofVerificationError(msg) => {
+                assert!(msg.starts_with("The upgrade path is wrong"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_upgrade_changing_client_type() {
+        let mut wl_storage = init_storage();
+        let client_id =
+            ClientId::new(ClientType::new("07-tendermint").unwrap(), 0)
+                .unwrap();
+        wl_storage
+            .write_log
+            .write(
+                &client_state_key(&client_id),
+                Protobuf::<Any>::encode_vec(tm_client_state(vec![
+                    "upgrade".to_string(),
+                    "upgradedIBCState".to_string(),
+                ])),
+            )
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+        // the client is upgraded to a mock client
+        let mut keys_changed = BTreeSet::new();
+        let key = write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(11),
+        );
+        keys_changed.insert(key);
+
+        let msg = MsgUpgradeClient {
+            client_id,
+            upgraded_client_state: mock_client_state(11).into(),
+            upgraded_consensus_state: MockConsensusState::new(
+                mock_client_state(11).header,
+            )
+            .into(),
+            proof_upgrade_client: dummy_proof(),
+            proof_upgrade_consensus_state: dummy_proof(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_upgrade_client(
+                &msg,
+                &Default::default(),
+                &mut ClientChecks::fail_fast(),
+            )
+            .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert!(
+                    msg.contains("The client type was changed by the upgrade")
+                )
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_client_tx_payload() {
+        let max_size =
+            ClientValidationConfig::default().max_client_tx_data_size;
+        let msg = MsgUpdateClient {
+            client_id: get_client_id(0),
+            client_message: MockHeader {
+                height: Height::new(0, 11).unwrap(),
+                timestamp: Timestamp::now(),
+            }
+            .into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx_data = msg.to_any().encode_to_vec();
+        assert!(matches!(
+            ClientTxPayload::decode(&tx_data, max_size).unwrap(),
+            ClientTxPayload::Update(decoded) if decoded == msg
+        ));
+
+        let msg = MsgUpgradeClient {
+            client_id: get_client_id(0),
+            upgraded_client_state: mock_client_state(11).into(),
+            upgraded_consensus_state: MockConsensusState::new(
+                mock_client_state(11).header,
+            )
+            .into(),
+            proof_upgrade_client: dummy_proof(),
+            proof_upgrade_consensus_state: dummy_proof(),
+            signer: "account0".to_string().into(),
+        };
+        let tx_data = msg.to_any().encode_to_vec();
+        assert!(matches!(
+            ClientTxPayload::decode(&tx_data, max_size).unwrap(),
+            ClientTxPayload::Upgrade(decoded) if decoded == msg
+        ));
+
+        // not a client message
+        assert!(matches!(
+            ClientTxPayload::decode(&[0xff; 8], max_size).unwrap(),
+            ClientTxPayload::Other
+        ));
+
+        // a malformed update message
+        let tx_data = Any {
+            type_url: "/ibc.core.client.v1.MsgUpdateClient".to_string(),
+            value: vec![0xff; 8],
+        }
+        .encode_to_vec();
+        let result = ClientTxPayload::decode(&tx_data, max_size).unwrap_err();
+        assert!(matches!(result, Error::Decoding(_)));
+    }
+
+    #[test]
+    fn test_oversized_client_tx_data() {
+        let encode = |type_url: &str, size: usize| {
+            Any {
+                type_url: type_url.to_string(),
+                value: vec![0xff; size],
+            }
+            .encode_to_vec()
+        };
+        let max_size =
+            ClientValidationConfig::default().max_client_tx_data_size;
+        let tx_data = encode(CLIENT_MSG_TYPE_URLS[1], max_size);
+        let result = ClientTxPayload::decode(&tx_data, max_size).unwrap_err();
+        assert!(matches!(result, Error::Decoding(_)));
+
+        // the data is rejected before decoding it, even if it isn't a client
+        // message
+        let tx_data = vec![0xff; max_size + 1];
+        let result = ClientTxPayload::decode(&tx_data, max_size).unwrap_err();
+        assert!(matches!(result, Error::Decoding(_)));
+        let tx_data = encode("/ibc.core.channel.v1.MsgRecvPacket", 8);
+        assert!(matches!(
+            ClientTxPayload::decode(&tx_data, tx_data.len()).unwrap(),
+            ClientTxPayload::Other
+        ));
+        let result =
+            ClientTxPayload::decode(&tx_data, tx_data.len() - 1).unwrap_err();
+        assert!(matches!(result, Error::Decoding(_)));
+
+        // the limit is configured for the validation
+        let wl_storage = init_storage();
+        let keys_changed = BTreeSet::new();
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.validate_client(&tx_data)
+            .expect("the data should be within the default limit");
+        let config = ClientValidationConfig {
+            max_client_tx_data_size: 4,
+            ..Default::default()
+        };
+        let result = ibc
+            .validate_client_with_config(&tx_data, &config, &NoopObserver)
+            .unwrap_err();
+        assert!(matches!(result, Error::Decoding(_)));
+        let (is_valid, errors) = ibc.validate_client_verbose(&tx_data, &config);
+        assert!(!is_valid);
+        assert!(matches!(errors[..], [Error::Decoding(_)]));
+    }
+
+    #[test]
+    fn test_verify_membership() {
+        let path = Path::ClientState(ClientStatePath(get_client_id(1)));
+        let value = b"value".to_vec();
+        // the proof fixture of the value in a Merkle tree
+        let mut tree = MerkleTree::<Sha256Hasher>::default();
+        let key = ibc_key(path.to_string()).unwrap();
+        tree.update(&key, &value).unwrap();
+        let sub_proof = match tree
+            .get_sub_tree_existence_proof(
+                std::array::from_ref(&key),
+                vec![value.as_slice()],
+            )
+            .unwrap()
+        {
+            MembershipProof::ICS23(proof) => proof,
+            _ => panic!("unexpected proof"),
+        };
+        let proof = tree.get_sub_tree_proof(&key, sub_proof).unwrap();
+        let proof = MerkleProof {
+            proofs: vec![proof.sub_proof, proof.base_proof],
+        };
+        let MerkleRoot(root) = tree.root();
+
+        let mut wl_storage = init_storage();
+        let client_id =
+            ClientId::new(ClientType::new("07-tendermint").unwrap(), 0)
+                .unwrap();
+        let height = Height::new(0, 10).unwrap();
+        wl_storage
+            .write_log
+            .write(
+                &client_state_key(&client_id),
+                Protobuf::<Any>::encode_vec(tm_client_state(vec![])),
+            )
+            .expect("write failed");
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&root),
+            TmTime::now(),
+            TmHash::Sha256([2; 32]),
+        )
+        .into();
+        wl_storage
+            .write_log
+            .write(
+                &consensus_state_key(&client_id, height),
+                AnyConsensusState::from(consensus_state).encode_vec(),
+            )
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let prefix = CommitmentPrefix::try_from(b"ibc".to_vec()).unwrap();
+        ibc.verify_membership(
+            &client_id, &prefix, &proof, &path, &value, height,
+        )
+        .expect("the membership should be verified");
+
+        let result = ibc
+            .verify_membership(
+                &client_id,
+                &prefix,
+                &proof,
+                &path,
+                b"other value",
+                height,
+            )
+            .unwrap_err();
+        assert!(matches!(result, Error::ProofVerificationError(_)));
+    }
+
+    #[test]
+    fn test_upgraded_states_with_different_metadata() {
+        let mut wl_storage = init_storage();
+        let client_id =
+            ClientId::new(ClientType::new("07-tendermint").unwrap(), 0)
+                .unwrap();
+        let upgraded_client_state = tm_client_state(vec![]);
+        let timestamp = TmTime::now();
+        let upgraded_consensus_state =
+            TmConsensusState::from(TmConsensusStateType::new(
+                CommitmentRoot::from_bytes(&[3; 32]),
+                timestamp,
+                TmHash::Sha256([4; 32]),
+            ));
+        // the stored states have the trusting period of the prior client
+        // state and the sentinel root
+        let mut stored_client_state = upgraded_client_state.inner().clone();
+        stored_client_state.trusting_period = Duration::from_secs(500);
+        let prior = AnyClientState::from(TmClientState::from(
+            stored_client_state.clone(),
+        ));
+        let client_key = client_state_key(&client_id);
+        wl_storage
+            .write_log
+            .write(
+                &client_key,
+                Protobuf::<Any>::encode_vec(TmClientState::from(
+                    stored_client_state.clone(),
+                )),
+            )
+            .expect("write failed");
+        let stored_consensus_state =
+            TmConsensusState::from(TmConsensusStateType::new(
+                CommitmentRoot::from_bytes(b"sentinel_root"),
+                timestamp,
+                TmHash::Sha256([4; 32]),
+            ));
+        wl_storage
+            .write_log
+            .write(
+                &consensus_state_key(&client_id, Height::new(0, 10).unwrap()),
+                AnyConsensusState::from(stored_consensus_state).encode_vec(),
+            )
+            .expect("write failed");
+
+        let msg = MsgUpgradeClient {
+            client_id,
+            upgraded_client_state: upgraded_client_state.into(),
+            upgraded_consensus_state: upgraded_consensus_state.into(),
+            proof_upgrade_client: dummy_proof(),
+            proof_upgrade_consensus_state: dummy_proof(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.validate_upgraded_states(&msg, &prior, false)
+                .expect("the upgrade semantics should match");
+            ibc.validate_upgraded_states(&msg, &prior, true)
+                .expect("the upgrade should be valid in the strict mode");
+        }
+
+        // the stored client state changes the trusting period of the prior
+        // client state
+        stored_client_state.trusting_period = Duration::from_secs(700);
+        wl_storage
+            .write_log
+            .write(
+                &client_key,
+                Protobuf::<Any>::encode_vec(TmClientState::from(
+                    stored_client_state,
+                )),
+            )
+            .expect("write failed");
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.validate_upgraded_states(&msg, &prior, false)
+            .expect("the upgrade semantics should match");
+        let result = ibc
+            .validate_upgraded_states(&msg, &prior, true)
+            .unwrap_err();
+        assert!(matches!(result, Error::ClientError(_)));
+    }
+
+    #[test]
+    fn test_tendermint_client_state_roundtrip() {
+        let client_state = test_tendermint_client_state(
+            ChainId::from_str("test-chain-0").unwrap(),
+            Height::new(0, 10).unwrap(),
+            Duration::from_secs(1000),
+        );
+        let AnyClientState::Tendermint(tm_client_state) = &client_state else {
+            panic!("unexpected client state: {client_state:?}");
+        };
+        let bytes = Protobuf::<Any>::encode_vec(tm_client_state.clone());
+        let decoded: TmClientState =
+            Protobuf::<Any>::decode_vec(&bytes).expect("decoding failed");
+        assert_eq!(decoded.inner(), tm_client_state.inner());
+        assert_eq!(decoded.inner().unbonding_period, Duration::from_secs(1500));
+
+        let decoded =
+            decode_client_state(&client_state_key(&get_client_id(0)), &bytes)
+                .expect("decoding failed");
+        assert_eq!(Any::from(decoded), Any::from(client_state));
+    }
+
+    #[test]
+    fn test_upgrade_unsupported_version() {
+        let mut upgraded_client_state = Any::from(tm_client_state(vec![]));
+        let mut msg = MsgUpgradeClient {
+            client_id: get_client_id(0),
+            upgraded_client_state: upgraded_client_state.clone(),
+            upgraded_consensus_state: MockConsensusState::new(
+                mock_client_state(11).header,
+            )
+            .into(),
+            proof_upgrade_client: dummy_proof(),
+            proof_upgrade_consensus_state: dummy_proof(),
+            signer: "account0".to_string().into(),
+        };
+        validate_upgraded_client_version(&msg)
+            .expect("the version should be supported");
+
+        upgraded_client_state.type_url =
+            "/ibc.lightclients.tendermint.v2.ClientState".to_string();
+        msg.upgraded_client_state = upgraded_client_state;
+        let wl_storage = init_storage();
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_upgrade_client(
+                &msg,
+                &Default::default(),
+                &mut ClientChecks::fail_fast(),
+            )
+            .unwrap_err();
+        assert!(matches!(result, Error::UnsupportedVersion(_)));
+    }
+
+    #[test]
+    fn test_header_chain_ids() {
+        let client_state = tm_client_state(vec![]).into();
+        validate_header_chain_ids(&client_state, ["test-chain-0"])
+            .expect("the header should belong to the client chain");
+
+        let result = validate_header_chain_ids(
+            &client_state,
+            ["test-chain-0", "other-chain-0", "test-chain-0"],
+        )
+        .unwrap_err();
+        match &result {
+            Error::HeaderError(msg) => assert!(msg.contains("header 1")),
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_client_height_rollback() {
+        let client_id = get_client_id(0);
+        let prior_height = Height::new(0, 10).unwrap();
+        for height in [10, 11] {
+            validate_no_rollback(
+                &client_id,
+                prior_height,
+                Height::new(0, height).unwrap(),
+            )
+            .expect("the client height shouldn't be rolled back");
+        }
+
+        let result = validate_no_rollback(
+            &client_id,
+            prior_height,
+            Height::new(0, 9).unwrap(),
+        )
+        .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert!(msg.contains("The client height was rolled back"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_proof_specs_unchanged() {
+        let prior = AnyClientState::from(tm_client_state(vec![]));
+        let mut posterior = tm_client_state(vec![]).inner().clone();
+        posterior.latest_height = Height::new(0, 11).unwrap();
+        validate_proof_specs_unchanged(
+            &prior,
+            &TmClientState::from(posterior.clone()).into(),
+        )
+        .expect("the proof specs should be unchanged");
+
+        posterior.proof_specs =
+            ibc_proof_specs::<Sha256Hasher>()[..1].to_vec().into();
+        let result = validate_proof_specs_unchanged(
+            &prior,
+            &TmClientState::from(posterior).into(),
+        )
+        .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert_eq!(msg, "The proof specs were changed by the update")
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_consensus_state_backfill() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let client_state = mock_client_state(10);
+        let now = Timestamp::now();
+        write_client_state(&mut wl_storage, &client_id, client_state);
+        wl_storage
+            .write_log
+            .write(
+                &consensus_state_key(&client_id, client_state.latest_height()),
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(
+                    MockHeader {
+                        height: client_state.latest_height(),
+                        timestamp: now,
+                    },
+                )),
+            )
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        // the client state is unchanged and a consensus state is added below
+        // the latest height
+        let height = Height::new(0, 8).unwrap();
+        let header = MockHeader {
+            height,
+            timestamp: (now - Duration::from_secs(10)).unwrap(),
+        };
+        let mut keys_changed = BTreeSet::new();
+        let key = consensus_state_key(&client_id, height);
+        wl_storage
+            .write_log
+            .write(
+                &key,
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(header)),
+            )
+            .expect("write failed");
+        keys_changed.insert(key);
+
+        let msg = MsgUpdateClient {
+            client_id: client_id.clone(),
+            client_message: header.into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.verify_update_client(&msg, false, &mut ClientChecks::fail_fast())
+            .expect("the backfill should be valid");
+
+        validate_consensus_state_backfill(
+            &client_id,
+            client_state.latest_height(),
+            [height],
+        )
+        .expect("the backfill should be below the latest height");
+        let result = validate_consensus_state_backfill(
+            &client_id,
+            client_state.latest_height(),
+            [height, Height::new(0, 12).unwrap()],
+        )
+        .unwrap_err();
+        assert!(matches!(result, Error::ClientError(_)));
+    }
+
+    #[test]
+    fn test_update_bumping_counter() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let msg = MsgUpdateClient {
+            client_id: client_id.clone(),
+            client_message: mock_client_state(11).header.into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let mut keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.validate_counter_unchanged()
+                .expect("the counter should be unchanged");
+        }
+
+        // the update also bumps the counter
+        let counter_key = client_counter_key();
+        wl_storage
+            .write_log
+            .write(&counter_key, 1u64.serialize_to_vec())
+            .expect("write failed");
+        keys_changed.insert(counter_key);
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_update_client(&msg, false, &mut ClientChecks::fail_fast())
+            .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert!(msg.starts_with("The client counter was changed"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_update_nonexistent_client() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        // the client state is only written by the transaction
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(11),
+        ));
+        let msg = MsgUpdateClient {
+            client_id,
+            client_message: mock_client_state(11).header.into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_update_client(&msg, false, &mut ClientChecks::fail_fast())
+            .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert!(msg.starts_with("update attempted on a nonexistent"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_update_created_client() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        // the transaction creates the client and updates it
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(11),
+        ));
+        let counter_key = client_counter_key();
+        wl_storage
+            .write_log
+            .write(&counter_key, 1u64.serialize_to_vec())
+            .expect("write failed");
+        keys_changed.insert(counter_key);
+        let msg = MsgUpdateClient {
+            client_id,
+            client_message: mock_client_state(12).header.into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_update_client(&msg, false, &mut ClientChecks::fail_fast())
+            .unwrap_err();
+        match &result {
+            Error::StateChange(msg) => {
+                assert!(msg.starts_with("client created and updated"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_trusted_consensus_state() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let height = Height::new(0, 10).unwrap();
+        wl_storage
+            .write_log
+            .write(
+                &consensus_state_key(&client_id, height),
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(
+                    MockHeader {
+                        height,
+                        timestamp: Timestamp::now(),
+                    },
+                )),
+            )
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.validate_trusted_consensus_state(&client_id, height)
+            .expect("the trusted consensus state should exist");
+        let result = ibc
+            .validate_trusted_consensus_state(
+                &client_id,
+                Height::new(0, 9).unwrap(),
+            )
+            .unwrap_err();
+        assert!(matches!(result, Error::ProofVerificationError(_)));
+    }
+
+    #[test]
+    fn test_update_with_corrupt_consensus_state() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        write_client_state(&mut wl_storage, &client_id, mock_client_state(10));
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        let mut keys_changed = BTreeSet::new();
+        let client_state = mock_client_state(11);
+        keys_changed.insert(write_client_state(
+            &mut wl_storage,
+            &client_id,
+            client_state,
+        ));
+        let key = consensus_state_key(&client_id, client_state.latest_height());
+        wl_storage
+            .write_log
+            .write(&key, vec![0xff; 8])
+            .expect("write failed");
+        keys_changed.insert(key);
+        let msg = MsgUpdateClient {
+            client_id,
+            client_message: client_state.header.into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_update_client(&msg, false, &mut ClientChecks::fail_fast())
+            .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => assert!(msg
+                .starts_with("The consensus state is present but undecodable")),
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_consensus_history_policy() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let now = Timestamp::now();
+        for height in [9, 10] {
+            let height = Height::new(0, height).unwrap();
+            wl_storage
+                .write_log
+                .write(
+                    &consensus_state_key(&client_id, height),
+                    Protobuf::<Any>::encode_vec(MockConsensusState::new(
+                        MockHeader {
+                            height,
+                            timestamp: now,
+                        },
+                    )),
+                )
+                .expect("write failed");
+        }
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+        let upgraded_height = Height::new(1, 1).unwrap();
+
+        let tx = Tx::from_type(TxType::Raw);
+        let mut keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.validate_consensus_history(
+                &client_id,
+                upgraded_height,
+                ConsensusHistoryPolicy::Retain,
+            )
+            .expect("the consensus states should be retained");
+            let result = ibc
+                .validate_consensus_history(
+                    &client_id,
+                    upgraded_height,
+                    ConsensusHistoryPolicy::Prune,
+                )
+                .unwrap_err();
+            assert!(matches!(result, Error::ClientError(_)));
+        }
+
+        // the upgrade prunes the prior consensus states
+        for height in [9, 10] {
+            let key = consensus_state_key(
+                &client_id,
+                Height::new(0, height).unwrap(),
+            );
+            wl_storage.write_log.delete(&key).expect("delete failed");
+            keys_changed.insert(key);
+        }
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.validate_consensus_history(
+            &client_id,
+            upgraded_height,
+            ConsensusHistoryPolicy::Prune,
+        )
+        .expect("the consensus states should be pruned");
+        let result = ibc
+            .validate_consensus_history(
+                &client_id,
+                upgraded_height,
+                ConsensusHistoryPolicy::Retain,
+            )
+            .unwrap_err();
+        assert!(matches!(result, Error::ClientError(_)));
+    }
+
+    #[test]
+    fn test_trusted_validators_hash() {
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&[1; 32]),
+            TmTime::now(),
+            TmHash::Sha256([1; 32]),
+        )
+        .into();
+        let trusted = AnyConsensusState::from(consensus_state);
+        validate_trusted_validators_hash(&trusted, &TmHash::Sha256([1; 32]))
+            .expect("the trusted validator set should match");
+        let result = validate_trusted_validators_hash(
+            &trusted,
+            &TmHash::Sha256([2; 32]),
+        )
+        .unwrap_err();
+        match &result {
+            Error::HeaderError(msg) => {
+                assert!(msg.starts_with("The trusted validator set mismatched"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_consensus_state_overwritten() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let height = Height::new(0, 10).unwrap();
+        let key = consensus_state_key(&client_id, height);
+        let now = Timestamp::now();
+        wl_storage
+            .write_log
+            .write(
+                &key,
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(
+                    MockHeader {
+                        height,
+                        timestamp: now,
+                    },
+                )),
+            )
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        // the consensus state is rewritten with another timestamp
+        wl_storage
+            .write_log
+            .write(
+                &key,
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(
+                    MockHeader {
+                        height,
+                        timestamp: (now + Duration::from_secs(1)).unwrap(),
+                    },
+                )),
+            )
+            .expect("write failed");
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(key.clone());
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let result = ibc
+                .validate_consensus_states_not_overwritten(&client_id)
+                .unwrap_err();
+            match &result {
+                Error::ClientError(msg) => {
+                    assert!(
+                        msg.starts_with("The consensus state was overwritten")
+                    )
+                }
+                _ => panic!("unexpected error: {result}"),
+            }
+        }
+
+        // pruning the consensus state is allowed
+        wl_storage.write_log.delete(&key).expect("delete failed");
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.validate_consensus_states_not_overwritten(&client_id)
+            .expect("the consensus state should be deletable");
+    }
+
+    #[test]
+    fn test_header_height() {
+        let trusted_height = Height::new(0, 10).unwrap();
+        validate_header_height(trusted_height, Height::new(0, 11).unwrap())
+            .expect("the header above the trusted height should be accepted");
+
+        for height in [9, 10] {
+            let header_height = Height::new(0, height).unwrap();
+            let result = validate_header_height(trusted_height, header_height)
+                .unwrap_err();
+            match &result {
+                Error::HeaderError(msg) => assert!(msg.contains(
+                    "The header doesn't advance from the trusted height"
+                )),
+                _ => panic!("unexpected error: {result}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_noop_update() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let client_state = mock_client_state(10);
+        let height = client_state.latest_height();
+        write_client_state(&mut wl_storage, &client_id, client_state);
+        wl_storage
+            .write_log
+            .write(
+                &consensus_state_key(&client_id, height),
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(
+                    client_state.header,
+                )),
+            )
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            // the header at the latest height was already applied
+            assert!(ibc.is_noop_update(&client_id, height).unwrap());
+            // no consensus state exists at the header height
+            assert!(!ibc
+                .is_noop_update(&client_id, Height::new(0, 11).unwrap())
+                .unwrap());
+        }
+
+        // the client state is changed by the update
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(11),
+        ));
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        assert!(!ibc.is_noop_update(&client_id, height).unwrap());
+    }
+
+    #[test]
+    fn test_update_freezing_client() {
+        let mut wl_storage = init_storage();
+        let now = TmTime::now();
+        let client_id = init_tm_client(&mut wl_storage, now);
+
+        // a conflicting header at the latest height freezes the client
+        let mut client_state = tm_client_state(vec![]).inner().clone();
+        client_state.frozen_height = Some(Height::new(0, 1).unwrap());
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(write_tm_client_state(
+            &mut wl_storage,
+            &client_id,
+            client_state,
+        ));
+        let msg = MsgUpdateClient {
+            client_id: client_id.clone(),
+            client_message: tm_header("test-chain-0", 10, 9, now).into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.verify_update_client(
+                &msg,
+                true,
+                &mut ClientChecks::fail_fast(),
+            )
+            .expect("the freeze should be valid");
+        }
+
+        // the freeze also adds a consensus state
+        keys_changed.insert(write_tm_consensus_state(
+            &mut wl_storage,
+            &client_id,
+            Height::new(0, 8).unwrap(),
+            [3; 32],
+            (now - Duration::from_secs(30)).unwrap(),
+        ));
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_update_client(&msg, true, &mut ClientChecks::fail_fast())
+            .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert!(msg
+                    .starts_with("The client was updated while being frozen"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_update_with_duplicate_header() {
+        let mut wl_storage = init_storage();
+        let now = TmTime::now();
+        let client_id = init_tm_client(&mut wl_storage, now);
+        let header = tm_header(
+            "test-chain-0",
+            11,
+            10,
+            (now - Duration::from_secs(5)).unwrap(),
+        );
+        let keys_changed =
+            write_tm_update(&mut wl_storage, &client_id, &header);
+        let tx_data = tm_update_tx_data(&client_id, header.clone());
+
+        // the same message twice in the transaction data is decoded as one
+        // message with one header
+        let tx_data = [tx_data.clone(), tx_data].concat();
+        let max_size =
+            ClientValidationConfig::default().max_client_tx_data_size;
+        match ClientTxPayload::decode(&tx_data, max_size).unwrap() {
+            ClientTxPayload::Update(msg) => {
+                assert_eq!(
+                    TmHeader::try_from(msg.client_message).unwrap(),
+                    header
+                )
+            }
+            payload => panic!("unexpected payload: {payload:?}"),
+        }
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.validate_client(&tx_data)
+            .expect("the update should be valid");
+    }
+
+    #[test]
+    fn test_height_ordering() {
+        let client_id = get_client_id(0);
+        // in the same revision
+        let prior = Height::new(0, 10).unwrap();
+        validate_no_rollback(&client_id, prior, Height::new(0, 10).unwrap())
+            .expect("the same height should be accepted");
+        validate_header_height(prior, Height::new(0, 11).unwrap())
+            .expect("the next height should advance");
+        let result =
+            validate_no_rollback(&client_id, prior, Height::new(0, 9).unwrap())
+                .unwrap_err();
+        assert!(matches!(result, Error::ClientError(_)));
+
+        // across revisions, the revision number takes precedence over the
+        // height in the revision
+        let prior = Height::new(1, 1).unwrap();
+        validate_header_height(Height::new(0, 100).unwrap(), prior)
+            .expect("the height of the next revision should advance");
+        let result = validate_no_rollback(
+            &client_id,
+            prior,
+            Height::new(0, 100).unwrap(),
+        )
+        .unwrap_err();
+        assert!(matches!(result, Error::ClientError(_)));
+        let result =
+            validate_header_height(prior, Height::new(0, 100).unwrap())
+                .unwrap_err();
+        assert!(matches!(result, Error::HeaderError(_)));
+    }
+
+    #[test]
+    fn test_connected_headers() {
+        let height = |h| Height::new(0, h).unwrap();
+        validate_connected_headers([
+            (height(10), height(11)),
+            (height(11), height(12)),
+            (height(12), height(15)),
+        ])
+        .expect("the headers should be connected");
+
+        // the third header skips the height of the second one
+        let result = validate_connected_headers([
+            (height(10), height(11)),
+            (height(11), height(12)),
+            (height(13), height(15)),
+        ])
+        .unwrap_err();
+        match &result {
+            Error::HeaderError(msg) => {
+                assert!(msg.starts_with("The header chain is disconnected"));
+                assert!(msg.contains("header 2"));
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+
+        // the single header is trusted at its own height
+        let result =
+            validate_connected_headers([(height(11), height(11))]).unwrap_err();
+        assert!(matches!(result, Error::HeaderError(_)));
+    }
+
+    #[test]
+    fn test_validate_client_with_tm_header() {
+        let mut wl_storage = init_storage();
+        let now = TmTime::now();
+        let client_id = init_tm_client(&mut wl_storage, now);
+        let time = (now - Duration::from_secs(5)).unwrap();
+        let header = tm_header("test-chain-0", 11, 10, time);
+        let keys_changed =
+            write_tm_update(&mut wl_storage, &client_id, &header);
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.validate_client(&tm_update_tx_data(&client_id, header.clone()))
+            .expect("the update should be valid");
+
+        let mut trusted_by_other_validators = header.clone();
+        trusted_by_other_validators.trusted_next_validator_set =
+            other_validator_set();
+        // the headers of the same update failing each header check
+        let invalid_headers = [
+            (
+                tm_header("other-chain-0", 11, 10, time),
+                "The header 0 belongs to another chain",
+            ),
+            (
+                tm_header("test-chain-0", 11, 11, time),
+                "The header chain is disconnected",
+            ),
+            (
+                tm_header("test-chain-0", 11, 7, time),
+                "The consensus state at the trusted height doesn't exist",
+            ),
+            (
+                trusted_by_other_validators,
+                "The trusted validator set mismatched",
+            ),
+            (
+                tm_header("test-chain-0", 10, 9, time),
+                "The header doesn't advance from the trusted height",
+            ),
+        ];
+        for (header, expected) in invalid_headers {
+            let result = ibc
+                .validate_client(&tm_update_tx_data(&client_id, header))
+                .unwrap_err();
+            match &result {
+                Error::HeaderError(msg)
+                | Error::ProofVerificationError(msg) => {
+                    assert!(msg.starts_with(expected), "{msg}")
+                }
+                _ => panic!("unexpected error: {result}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_client_anchoring_root_to_tm_header() {
+        let mut wl_storage = init_storage();
+        let now = TmTime::now();
+        let client_id = init_tm_client(&mut wl_storage, now);
+        let time = (now - Duration::from_secs(5)).unwrap();
+        let header = tm_header("test-chain-0", 11, 10, time);
+        let keys_changed =
+            write_tm_update(&mut wl_storage, &client_id, &header);
+        let tx_data = tm_update_tx_data(&client_id, header);
+        let config = ClientValidationConfig {
+            deep_verification: true,
+            ..Default::default()
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.validate_client_with_config(&tx_data, &config, &NoopObserver)
+                .expect("the root should be anchored to the header");
+        }
+
+        // the stored consensus state has a root other than the app hash of
+        // the header
+        write_tm_consensus_state(
+            &mut wl_storage,
+            &client_id,
+            Height::new(0, 11).unwrap(),
+            [3; 32],
+            time,
+        );
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.validate_client(&tx_data)
+            .expect("the root should be checked only by the deep verification");
+        let result = ibc
+            .validate_client_with_config(&tx_data, &config, &NoopObserver)
+            .unwrap_err();
+        match &result {
+            Error::HeaderError(msg) => {
+                assert!(msg
+                    .starts_with("The consensus root doesn't match the header"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_update_tm_header_noop_and_backfill() {
+        let mut wl_storage = init_storage();
+        let now = TmTime::now();
+        let client_id = init_tm_client(&mut wl_storage, now);
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+
+        // backfill the consensus state at the height 7 with a header trusted
+        // at the height 5
+        let time = (now - Duration::from_secs(40)).unwrap();
+        let header = tm_header("test-chain-0", 7, 5, time);
+        let keys_changed =
+            write_tm_update(&mut wl_storage, &client_id, &header);
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.validate_client(&tm_update_tx_data(&client_id, header))
+                .expect("the backfill should be accepted");
+        }
+
+        // a consensus state above the latest height without the client update
+        wl_storage.write_log.drop_tx();
+        let time = (now - Duration::from_secs(15)).unwrap();
+        let header = tm_header("test-chain-0", 11, 10, time);
+        let keys_changed = BTreeSet::from([write_tm_consensus_state(
+            &mut wl_storage,
+            &client_id,
+            Height::new(0, 11).unwrap(),
+            [1; 32],
+            time,
+        )]);
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let result = ibc
+                .validate_client(&tm_update_tx_data(&client_id, header))
+                .unwrap_err();
+            match &result {
+                Error::ClientError(msg) => {
+                    assert!(msg.starts_with("Invalid consensus state backfill"))
+                }
+                _ => panic!("unexpected error: {result}"),
+            }
+        }
+
+        // the header at the height 11 has been applied
+        wl_storage.write_log.drop_tx();
+        let time = (now - Duration::from_secs(5)).unwrap();
+        let header = tm_header("test-chain-0", 11, 10, time);
+        write_tm_update(&mut wl_storage, &client_id, &header);
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+        set_block_time(&mut wl_storage, now);
+        let keys_changed = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.validate_client(&tm_update_tx_data(&client_id, header.clone()))
+                .expect("the resubmitted header should be a no-op");
+        }
+
+        // the resubmitted header changes the client
+        let next_header = tm_header(
+            "test-chain-0",
+            12,
+            11,
+            (now - Duration::from_secs(1)).unwrap(),
+        );
+        let keys_changed =
+            write_tm_update(&mut wl_storage, &client_id, &next_header);
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .validate_client(&tm_update_tx_data(&client_id, header))
+            .unwrap_err();
+        match &result {
+            Error::HeaderError(msg) => {
+                assert!(msg.starts_with(
+                    "The header doesn't advance from the trusted height"
+                ))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_update_trusting_consensus_state_in_tx() {
+        let mut wl_storage = init_storage();
+        let now = TmTime::now();
+        let client_id = init_tm_client(&mut wl_storage, now);
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let time = (now - Duration::from_secs(5)).unwrap();
+        let header = tm_header("test-chain-0", 11, 10, time);
+        let mut keys_changed =
+            write_tm_update(&mut wl_storage, &client_id, &header);
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.validate_client(&tm_update_tx_data(&client_id, header))
+                .expect("the trusted consensus state should exist");
+        }
+
+        // the header is trusted at the height 11 which is written only by
+        // the same transaction
+        let header = tm_header(
+            "test-chain-0",
+            12,
+            11,
+            (now - Duration::from_secs(1)).unwrap(),
+        );
+        keys_changed.extend(write_tm_update(
+            &mut wl_storage,
+            &client_id,
+            &header,
+        ));
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .validate_client(&tm_update_tx_data(&client_id, header))
+            .unwrap_err();
+        match &result {
+            Error::ProofVerificationError(msg) => {
+                assert!(msg.starts_with(
+                    "The consensus state at the trusted height doesn't exist"
+                ))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_deep_verification_with_trusted_validator_set() {
+        let mut wl_storage = init_storage();
+        let now = TmTime::now();
+        let client_id = init_tm_client(&mut wl_storage, now);
+        let time = (now - Duration::from_secs(5)).unwrap();
+        let header = tm_header("test-chain-0", 11, 10, time);
+        let keys_changed =
+            write_tm_update(&mut wl_storage, &client_id, &header);
+        let config = ClientValidationConfig {
+            deep_verification: true,
+            ..Default::default()
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let tx_data = tm_update_tx_data(&client_id, header.clone());
+            ibc.validate_client_with_config(&tx_data, &config, &NoopObserver)
+                .expect("the update should pass the deep verification");
+        }
+
+        // the header is trusted by another validator set than the trusted
+        // consensus state
+        let mut header = header;
+        header.trusted_next_validator_set = other_validator_set();
+        let tx_data = tm_update_tx_data(&client_id, header);
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let result = ibc
+                .validate_client_with_config(&tx_data, &config, &NoopObserver)
+                .unwrap_err();
+            match &result {
+                Error::HeaderError(msg) => {
+                    assert!(
+                        msg.starts_with("The trusted validator set mismatched")
+                    )
+                }
+                _ => panic!("unexpected error: {result}"),
+            }
+        }
+
+        // the stored root isn't the app hash of the header either
+        write_tm_consensus_state(
+            &mut wl_storage,
+            &client_id,
+            Height::new(0, 11).unwrap(),
+            [3; 32],
+            time,
+        );
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let (is_valid, errors) = ibc.validate_client_verbose(&tx_data, &config);
+        assert!(!is_valid);
+        let messages: Vec<_> = errors
+            .iter()
+            .map(|error| match error {
+                Error::HeaderError(msg) => msg.as_str(),
+                _ => panic!("unexpected error: {error}"),
+            })
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("The trusted validator set mismatched"));
+        assert!(messages[1]
+            .starts_with("The consensus root doesn't match the header"));
+    }
+
+    #[test]
+    fn test_update_with_disconnected_tm_header() {
+        let mut wl_storage = init_storage();
+        let now = TmTime::now();
+        let client_id = init_tm_client(&mut wl_storage, now);
+        let time = (now - Duration::from_secs(5)).unwrap();
+        let header = tm_header("test-chain-0", 11, 10, time);
+        let keys_changed =
+            write_tm_update(&mut wl_storage, &client_id, &header);
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.validate_client(&tm_update_tx_data(&client_id, header))
+                .expect("the header should be connected");
+        }
+
+        // the header is trusted at its own height, or above it
+        for trusted_height in [11, 12] {
+            let header = tm_header("test-chain-0", 11, trusted_height, time);
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let result = ibc
+                .validate_client(&tm_update_tx_data(&client_id, header))
+                .unwrap_err();
+            match &result {
+                Error::HeaderError(msg) => {
+                    assert!(msg.starts_with("The header chain is disconnected"))
+                }
+                _ => panic!("unexpected error: {result}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_header_timestamp() {
+        let block_time = Timestamp::now();
+        let max_clock_drift = Duration::from_secs(10);
+        let within_drift = (block_time + Duration::from_secs(5)).unwrap();
+        validate_header_timestamp(within_drift, block_time, max_clock_drift)
+            .expect("the header within the drift should be accepted");
+
+        let beyond_drift = (block_time + Duration::from_secs(11)).unwrap();
+        let result = validate_header_timestamp(
+            beyond_drift,
+            block_time,
+            max_clock_drift,
+        )
+        .unwrap_err();
+        assert!(matches!(result, Error::HeaderError(_)));
+    }
+
+    #[test]
+    fn test_update_with_future_tm_header() {
+        let mut wl_storage = init_storage();
+        let now = TmTime::now();
+        let client_id = init_tm_client(&mut wl_storage, now);
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+
+        // the header is ahead of the block within the max clock drift 60s
+        let time = (now + Duration::from_secs(30)).unwrap();
+        let header = tm_header("test-chain-0", 11, 10, time);
+        let keys_changed =
+            write_tm_update(&mut wl_storage, &client_id, &header);
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.validate_client(&tm_update_tx_data(&client_id, header))
+                .expect("the header within the drift should be accepted");
+        }
+
+        // the header is ahead of the block beyond the max clock drift
+        wl_storage.write_log.drop_tx();
+        let time = (now + Duration::from_secs(90)).unwrap();
+        let header = tm_header("test-chain-0", 11, 10, time);
+        let keys_changed =
+            write_tm_update(&mut wl_storage, &client_id, &header);
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .validate_client(&tm_update_tx_data(&client_id, header))
+            .unwrap_err();
+        match &result {
+            Error::HeaderError(msg) => {
+                assert!(msg.starts_with("The header is too far in the future"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_decode_merkle_proof() {
+        decode_merkle_proof("client", &dummy_proof())
+            .expect("the proof should be decoded");
+
+        // a Merkle proof with one empty commitment proof
+        let empty = CommitmentProofBytes::try_from(vec![0x0a, 0x00]).unwrap();
+        let result = decode_merkle_proof("client", &empty).unwrap_err();
+        assert!(matches!(result, Error::IbcDataError(_)));
+
+        let malformed = CommitmentProofBytes::try_from(vec![0xff; 4]).unwrap();
+        let result = decode_merkle_proof("client", &malformed).unwrap_err();
+        assert!(matches!(result, Error::IbcDataError(_)));
+    }
+
+    #[test]
+    fn test_trusting_period() {
+        let mut wl_storage = init_storage();
+        let tm_client_id =
+            ClientId::new(ClientType::new("07-tendermint").unwrap(), 0)
+                .unwrap();
+        let key = client_state_key(&tm_client_id);
+        wl_storage
+            .write_log
+            .write(&key, Protobuf::<Any>::encode_vec(tm_client_state(vec![])))
+            .expect("write failed");
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(key);
+        let mock_client_id = get_client_id(1);
+        let key = write_client_state(
+            &mut wl_storage,
+            &mock_client_id,
+            mock_client_state(10),
+        );
+        keys_changed.insert(key);
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        assert_eq!(
+            ibc.trusting_period(&tm_client_id),
+            Some(Duration::from_secs(1000))
+        );
+        assert_eq!(ibc.trusting_period(&mock_client_id), None);
+        assert_eq!(ibc.trusting_period(&get_client_id(2)), None);
+    }
+
+    #[test]
+    fn test_upgrade_without_proofs() {
+        let wl_storage = init_storage();
+        // a Merkle proof with one empty commitment proof
+        let empty = CommitmentProofBytes::try_from(vec![0x0a, 0x00]).unwrap();
+        let msg = MsgUpgradeClient {
+            // the client doesn't even exist
+            client_id: get_client_id(0),
+            upgraded_client_state: mock_client_state(11).into(),
+            upgraded_consensus_state: MockConsensusState::new(
+                mock_client_state(11).header,
+            )
+            .into(),
+            proof_upgrade_client: dummy_proof(),
+            proof_upgrade_consensus_state: empty,
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_upgrade_client(
+                &msg,
+                &Default::default(),
+                &mut ClientChecks::fail_fast(),
+            )
+            .unwrap_err();
+        assert!(matches!(result, Error::IbcDataError(_)));
+    }
+
+    #[test]
+    fn test_upgrade_with_malformed_proof() {
+        let wl_storage = init_storage();
+        // a Merkle proof with a non-existence proof without any neighbour
+        let malformed =
+            CommitmentProofBytes::try_from(vec![0x0a, 0x02, 0x12, 0x00])
+                .unwrap();
+        let msg = MsgUpgradeClient {
+            client_id: get_client_id(0),
+            upgraded_client_state: mock_client_state(11).into(),
+            upgraded_consensus_state: MockConsensusState::new(
+                mock_client_state(11).header,
+            )
+            .into(),
+            proof_upgrade_client: malformed,
+            proof_upgrade_consensus_state: dummy_proof(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_upgrade_client(
+                &msg,
+                &Default::default(),
+                &mut ClientChecks::fail_fast(),
+            )
+            .unwrap_err();
+        match &result {
+            Error::ProofVerificationError(msg) => {
+                assert!(msg.starts_with("The proof structure is malformed"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+
+        // an existence proof without a key
+        let malformed =
+            CommitmentProofBytes::try_from(vec![0x0a, 0x02, 0x0a, 0x00])
+                .unwrap();
+        let result = decode_merkle_proof("client", &malformed).unwrap_err();
+        assert!(matches!(result, Error::ProofVerificationError(_)));
+    }
+
+    #[test]
+    fn test_upgrade_with_oversized_proof() {
+        let wl_storage = init_storage();
+        let max_size = ClientValidationConfig::default().max_upgrade_proof_size;
+        let oversized =
+            CommitmentProofBytes::try_from(vec![0x0a; max_size + 1]).unwrap();
+        let msg = MsgUpgradeClient {
+            client_id: get_client_id(0),
+            upgraded_client_state: mock_client_state(11).into(),
+            upgraded_consensus_state: MockConsensusState::new(
+                mock_client_state(11).header,
+            )
+            .into(),
+            proof_upgrade_client: oversized,
+            proof_upgrade_consensus_state: dummy_proof(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_upgrade_client(
+                &msg,
+                &Default::default(),
+                &mut ClientChecks::fail_fast(),
+            )
+            .unwrap_err();
+        match &result {
+            Error::ProofVerificationError(msg) => {
+                assert!(msg.starts_with("The proof is too large"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+
+        // the proof is within a larger configured limit
+        let config = ClientValidationConfig {
+            max_upgrade_proof_size: 2 * max_size,
+            ..Default::default()
+        };
+        let result = ibc
+            .verify_upgrade_client(
+                &msg,
+                &config,
+                &mut ClientChecks::fail_fast(),
+            )
+            .unwrap_err();
+        assert!(!result.to_string().contains("The proof is too large"));
+
+        // the dummy proofs exceed a smaller configured limit
+        let msg = MsgUpgradeClient {
+            proof_upgrade_client: dummy_proof(),
+            ..msg
+        };
+        let config = ClientValidationConfig {
+            max_upgrade_proof_size: dummy_proof().as_ref().len() - 1,
+            ..Default::default()
+        };
+        let result = ibc
+            .verify_upgrade_client(
+                &msg,
+                &config,
+                &mut ClientChecks::fail_fast(),
+            )
+            .unwrap_err();
+        match &result {
+            Error::ProofVerificationError(msg) => {
+                assert!(msg.starts_with("The proof is too large"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+
+        let proof = dummy_proof();
+        let size = proof.as_ref().len();
+        validate_proof_size("client", &proof, size)
+            .expect("the proof should be small enough");
+        let result =
+            validate_proof_size("client", &proof, size - 1).unwrap_err();
+        assert!(matches!(result, Error::ProofVerificationError(_)));
+    }
+
+    #[test]
+    fn test_client_counter_format() {
+        let mut wl_storage = init_storage();
+        let counter_key = client_counter_key();
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(counter_key.clone());
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+
+        // a counter written as u32
+        wl_storage
+            .write_log
+            .write(&counter_key, 1u32.serialize_to_vec())
+            .expect("write failed");
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let result = ibc.client_counter().unwrap_err();
+            assert!(matches!(result, Error::ClientError(_)));
+            assert_eq!(ibc.client_counter_pre().unwrap(), 0);
+        }
+
+        // a counter which can't be incremented is still decoded, only the
+        // creation from it is rejected
+        wl_storage
+            .write_log
+            .write(&counter_key, u64::MAX.serialize_to_vec())
+            .expect("write failed");
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            assert_eq!(ibc.client_counter().unwrap(), u64::MAX);
+        }
+
+        wl_storage
+            .write_log
+            .write(&counter_key, 1u64.serialize_to_vec())
+            .expect("write failed");
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        assert_eq!(ibc.client_counter().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_next_client_id() {
+        let mut wl_storage = init_storage();
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let keys_changed = BTreeSet::new();
+        let next_client_id = {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.next_client_id(client_type()).unwrap()
+        };
+        assert_eq!(next_client_id.as_str(), format!("{}-0", client_type()));
+
+        // the creation stores the client with the ID and increments the
+        // counter
+        let mut keys_changed = BTreeSet::new();
+        let key = write_client_state(
+            &mut wl_storage,
+            &next_client_id,
+            mock_client_state(10),
+        );
+        keys_changed.insert(key);
+        let counter_key = client_counter_key();
+        wl_storage
+            .write_log
+            .write(&counter_key, 1u64.serialize_to_vec())
+            .expect("write failed");
+        keys_changed.insert(counter_key);
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        assert_eq!(ibc.iter_client_ids().unwrap(), vec![next_client_id]);
+        assert_eq!(
+            ibc.next_client_id(client_type()).unwrap(),
+            get_client_id(1)
+        );
+    }
+
+    #[test]
+    fn test_create_client_conflicting_consensus_state() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let height = Height::new(0, 10).unwrap();
+        let header = MockHeader {
+            height,
+            timestamp: Timestamp::now(),
+        };
+        // the consensus state at the same height has another timestamp
+        let divergent = MockHeader {
+            height,
+            timestamp: (header.timestamp + Duration::from_secs(1)).unwrap(),
+        };
+        let client_state = MockClientState::new(header);
+        let mut keys_changed = BTreeSet::new();
+        let key = write_client_state(&mut wl_storage, &client_id, client_state);
+        keys_changed.insert(key);
+        let consensus_key = consensus_state_key(&client_id, height);
+        wl_storage
+            .write_log
+            .write(
+                &consensus_key,
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(divergent)),
+            )
+            .expect("write failed");
+        keys_changed.insert(consensus_key.clone());
+
+        let msg = MsgCreateClient {
+            client_state: client_state.into(),
+            consensus_state: MockConsensusState::new(header).into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let result = ibc
+                .verify_create_client(
+                    &msg,
+                    None,
+                    &mut ClientChecks::fail_fast(),
+                )
+                .unwrap_err();
+            assert!(matches!(result, Error::ClientError(_)));
+        }
+
+        wl_storage
+            .write_log
+            .write(
+                &consensus_key,
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(header)),
+            )
+            .expect("write failed");
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.verify_create_client(&msg, None, &mut ClientChecks::fail_fast())
+            .expect("the consensus state should be the same");
+    }
+
+    #[test]
+    fn test_create_client_stray_consensus_state() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let height = Height::new(0, 10).unwrap();
+        let header = MockHeader {
+            height,
+            timestamp: Timestamp::now(),
+        };
+        let client_state = MockClientState::new(header);
+        let mut keys_changed = BTreeSet::new();
+        let key = write_client_state(&mut wl_storage, &client_id, client_state);
+        keys_changed.insert(key);
+        let consensus_key = consensus_state_key(&client_id, height);
+        wl_storage
+            .write_log
+            .write(
+                &consensus_key,
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(header)),
+            )
+            .expect("write failed");
+        keys_changed.insert(consensus_key);
+
+        let msg = MsgCreateClient {
+            client_state: client_state.into(),
+            consensus_state: MockConsensusState::new(header).into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.verify_create_client(
+                &msg,
+                None,
+                &mut ClientChecks::fail_fast(),
+            )
+            .expect("the consensus state should be of the client type");
+        }
+
+        // a stray Tendermint consensus state for the mock client
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&[1; 32]),
+            TmTime::now(),
+            TmHash::Sha256([2; 32]),
+        )
+        .into();
+        let stray_key =
+            consensus_state_key(&client_id, Height::new(0, 9).unwrap());
+        wl_storage
+            .write_log
+            .write(
+                &stray_key,
+                AnyConsensusState::from(consensus_state).encode_vec(),
+            )
+            .expect("write failed");
+        keys_changed.insert(stray_key);
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_create_client(&msg, None, &mut ClientChecks::fail_fast())
+            .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert!(msg.contains("doesn't match the client type"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_create_client_consensus_state_count() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let header = mock_client_state(10).header;
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(10),
+        ));
+        let msg = MsgCreateClient {
+            client_state: mock_client_state(10).into(),
+            consensus_state: MockConsensusState::new(header).into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let assert_count_error = |result: VpResult<()>| match &result {
+            Err(Error::ClientError(msg)) => assert!(msg.starts_with(
+                "The number of the consensus states of the created client is \
+                 unexpected"
+            )),
+            _ => panic!("unexpected result: {result:?}"),
+        };
+        // no consensus state
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            assert_count_error(ibc.verify_create_client(
+                &msg,
+                None,
+                &mut ClientChecks::fail_fast(),
+            ));
+        }
+
+        // the consensus states at the latest height and below it
+        for height in [10, 9] {
+            let header = MockHeader {
+                height: Height::new(0, height).unwrap(),
+                timestamp: header.timestamp,
+            };
+            let key = consensus_state_key(&client_id, header.height);
+            wl_storage
+                .write_log
+                .write(
+                    &key,
+                    Protobuf::<Any>::encode_vec(MockConsensusState::new(
+                        header,
+                    )),
+                )
+                .expect("write failed");
+            keys_changed.insert(key);
+        }
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        assert_count_error(ibc.verify_create_client(
+            &msg,
+            None,
+            &mut ClientChecks::fail_fast(),
+        ));
+    }
+
+    #[test]
+    fn test_latest_consensus_state_newest() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let now = Timestamp::now();
+        let latest: AnyConsensusState = MockConsensusState::new(MockHeader {
+            height: Height::new(0, 11).unwrap(),
+            timestamp: now,
+        })
+        .into();
+        let mut keys_changed = BTreeSet::new();
+        // the consensus state at the higher height `0-12` is older
+        for (height, timestamp) in [
+            (10, (now - Duration::from_secs(1)).unwrap()),
+            (11, now),
+            (12, (now - Duration::from_secs(2)).unwrap()),
+        ] {
+            let height = Height::new(0, height).unwrap();
+            let key = consensus_state_key(&client_id, height);
+            let consensus_state =
+                MockConsensusState::new(MockHeader { height, timestamp });
+            wl_storage
+                .write_log
+                .write(&key, Protobuf::<Any>::encode_vec(consensus_state))
+                .expect("write failed");
+            keys_changed.insert(key);
+        }
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.validate_latest_consensus_state_newest(&client_id, &latest)
+                .expect("the latest consensus state should be the newest");
+        }
+
+        // the corrupted consensus state at `0-12` is newer than the latest
+        let height = Height::new(0, 12).unwrap();
+        let consensus_state = MockConsensusState::new(MockHeader {
+            height,
+            timestamp: (now + Duration::from_secs(1)).unwrap(),
+        });
+        wl_storage
+            .write_log
+            .write(
+                &consensus_state_key(&client_id, height),
+                Protobuf::<Any>::encode_vec(consensus_state),
+            )
+            .expect("write failed");
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .validate_latest_consensus_state_newest(&client_id, &latest)
+            .unwrap_err();
+        match &result {
+            Error::HeaderError(msg) => {
+                assert!(
+                    msg.contains("The latest consensus state isn't the newest")
+                )
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_query_client_state() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        write_client_state(&mut wl_storage, &client_id, mock_client_state(10));
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        for id in [client_id, get_client_id(1)] {
+            let queried =
+                query_client_state(&wl_storage, &id).unwrap().map(Any::from);
+            let validated = ibc.client_state_post(&id).unwrap().map(Any::from);
+            assert_eq!(queried, validated);
+        }
+        assert!(query_client_state(&wl_storage, &get_client_id(0))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_create_frozen_client() {
+        let wl_storage = init_storage();
+        let client_state = TmClientState::from(
+            tm_client_state(vec![])
+                .inner()
+                .clone()
+                .with_frozen_height(Height::new(0, 5).unwrap()),
+        );
+        let consensus_state =
+            TmConsensusState::from(TmConsensusStateType::new(
+                CommitmentRoot::from_bytes(&[1; 32]),
+                TmTime::now(),
+                TmHash::Sha256([2; 32]),
+            ));
+        let msg = MsgCreateClient {
+            client_state: client_state.into(),
+            consensus_state: consensus_state.into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_create_client(&msg, None, &mut ClientChecks::fail_fast())
+            .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert_eq!(msg, "The client was created already frozen")
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_consensus_states_at() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let now = Timestamp::now();
+        for height in [9, 10] {
+            let height = Height::new(0, height).unwrap();
+            let consensus_state = MockConsensusState::new(MockHeader {
+                height,
+                timestamp: now,
+            });
+            wl_storage
+                .write_log
+                .write(
+                    &consensus_state_key(&client_id, height),
+                    Protobuf::<Any>::encode_vec(consensus_state),
+                )
+                .expect("write failed");
+        }
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let heights = [10, 11, 9].map(|height| Height::new(0, height).unwrap());
+        let consensus_states =
+            ibc.consensus_states_at(&client_id, &heights).unwrap();
+        assert_eq!(consensus_states.len(), 3);
+        for (height, consensus_state) in heights.iter().zip(&consensus_states) {
+            let expected =
+                ibc.consensus_state_pre(&client_id, *height).unwrap();
+            assert_eq!(
+                consensus_state.as_ref().map(|cs| cs.encode_vec()),
+                expected.map(|cs| cs.encode_vec())
+            );
+        }
+        assert!(consensus_states[0].is_some());
+        assert!(consensus_states[1].is_none());
+        assert!(consensus_states[2].is_some());
+    }
+
+    #[test]
+    fn test_zero_timestamp() {
+        let header = MockHeader {
+            height: Height::new(0, 10).unwrap(),
+            timestamp: Timestamp::from_nanoseconds(0).unwrap(),
+        };
+        let msg = MsgCreateClient {
+            client_state: MockClientState::new(header).into(),
+            consensus_state: MockConsensusState::new(header).into(),
+            signer: "account0".to_string().into(),
+        };
+        let wl_storage = init_storage();
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_create_client(&msg, None, &mut ClientChecks::fail_fast())
+            .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert_eq!(msg, "The timestamp is zero")
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+
+        // the consensus state stored by an update
+        let consensus_state: AnyConsensusState =
+            MockConsensusState::new(header).into();
+        let result = validate_nonzero_timestamp(&consensus_state).unwrap_err();
+        assert!(matches!(result, Error::ClientError(_)));
+        let consensus_state: AnyConsensusState =
+            MockConsensusState::new(mock_client_state(10).header).into();
+        validate_nonzero_timestamp(&consensus_state)
+            .expect("the timestamp should be valid");
+    }
+
+    #[test]
+    fn test_create_client_counter_overflow() {
+        let mut wl_storage = init_storage();
+        wl_storage
+            .write_log
+            .write(&client_counter_key(), u64::MAX.serialize_to_vec())
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        let header = mock_client_state(10).header;
+        let msg = MsgCreateClient {
+            client_state: mock_client_state(10).into(),
+            consensus_state: MockConsensusState::new(header).into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_create_client(&msg, None, &mut ClientChecks::fail_fast())
+            .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert!(msg.starts_with("The client counter overflowed"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_create_client_zero_root() {
+        let wl_storage = init_storage();
+        let consensus_state =
+            TmConsensusState::from(TmConsensusStateType::new(
+                CommitmentRoot::from_bytes(&[0; 32]),
+                TmTime::now(),
+                TmHash::Sha256([2; 32]),
+            ));
+        let msg = MsgCreateClient {
+            client_state: tm_client_state(vec![]).into(),
+            consensus_state: consensus_state.into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_create_client(&msg, None, &mut ClientChecks::fail_fast())
+            .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert_eq!(
+                    msg,
+                    "The commitment root at the latest height is zero"
+                )
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_created_client_keys() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(1);
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(10),
+        ));
+        keys_changed.insert(client_counter_key());
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.validate_created_client_keys(&client_id)
+                .expect("the keys should be for the created client");
+        }
+
+        // the consensus state is written for another client whose ID shares
+        // the prefix of the created one
+        let other_id = get_client_id(10);
+        let height = Height::new(0, 10).unwrap();
+        let key = consensus_state_key(&other_id, height);
+        wl_storage
+            .write_log
+            .write(
+                &key,
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(
+                    MockHeader {
+                        height,
+                        timestamp: Timestamp::now(),
+                    },
+                )),
+            )
+            .expect("write failed");
+        keys_changed.insert(key);
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc.validate_created_client_keys(&client_id);
+        assert!(matches!(result, Err(Error::KeyError { .. })));
+    }
+
+    #[test]
+    fn test_create_client_unexpected_chain_id() {
+        let wl_storage = init_storage();
+        let consensus_state =
+            TmConsensusState::from(TmConsensusStateType::new(
+                CommitmentRoot::from_bytes(&[1; 32]),
+                TmTime::now(),
+                TmHash::Sha256([2; 32]),
+            ));
+        let msg = MsgCreateClient {
+            client_state: tm_client_state(vec![]).into(),
+            consensus_state: consensus_state.into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let keys_changed = BTreeSet::new();
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let mut tx_data = vec![];
+        msg.to_any().encode(&mut tx_data).expect("encoding failed");
+        let config = ClientValidationConfig {
+            expected_counterparty_chain_id: Some(
+                ChainId::from_str("other-chain-0").unwrap(),
+            ),
+            ..Default::default()
+        };
+        let result = ibc
+            .validate_client_with_config(&tx_data, &config, &NoopObserver)
+            .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert!(msg.contains("The counterparty chain ID is unexpected"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+
+        let client_state = tm_client_state(vec![]).into();
+        validate_counterparty_chain_id(
+            &client_state,
+            &ChainId::from_str("test-chain-0").unwrap(),
+        )
+        .expect("the chain ID should be expected");
+    }
+
+    #[test]
+    fn test_client_storage_size() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let mut keys_changed = BTreeSet::new();
+        let key = client_state_key(&client_id);
+        let bytes = Protobuf::<Any>::encode_vec(mock_client_state(10));
+        let mut expected = bytes.len();
+        wl_storage
+            .write_log
+            .write(&key, bytes)
+            .expect("write failed");
+        keys_changed.insert(key);
+        for height in [9, 10] {
+            let height = Height::new(0, height).unwrap();
+            let key = consensus_state_key(&client_id, height);
+            let consensus_state = MockConsensusState::new(MockHeader {
+                height,
+                timestamp: Timestamp::now(),
+            });
+            let bytes = Protobuf::<Any>::encode_vec(consensus_state);
+            expected += bytes.len();
+            wl_storage
+                .write_log
+                .write(&key, bytes)
+                .expect("write failed");
+            keys_changed.insert(key);
+        }
+        // the client counter written at the genesis
+        expected += 0u64.serialize_to_vec().len();
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        assert_eq!(ibc.client_storage_size(&client_id).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_consensus_state_revisions() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let mut keys_changed = BTreeSet::new();
+        for (revision_number, revision_height) in [(0, 9), (0, 10)] {
+            let height = Height::new(revision_number, revision_height).unwrap();
+            let key = consensus_state_key(&client_id, height);
+            let consensus_state = MockConsensusState::new(MockHeader {
+                height,
+                timestamp: Timestamp::now(),
+            });
+            wl_storage
+                .write_log
+                .write(&key, Protobuf::<Any>::encode_vec(consensus_state))
+                .expect("write failed");
+            keys_changed.insert(key);
+        }
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.validate_consensus_state_revisions(&client_id, 0)
+                .expect("the revisions should match");
+        }
+
+        // a consensus state of another revision
+        let height = Height::new(1, 1).unwrap();
+        let key = consensus_state_key(&client_id, height);
+        let consensus_state = MockConsensusState::new(MockHeader {
+            height,
+            timestamp: Timestamp::now(),
+        });
+        wl_storage
+            .write_log
+            .write(&key, Protobuf::<Any>::encode_vec(consensus_state))
+            .expect("write failed");
+        keys_changed.insert(key);
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .validate_consensus_state_revisions(&client_id, 0)
+            .unwrap_err();
+        assert!(matches!(result, Error::ClientError(_)));
+    }
+
+    #[test]
+    fn test_update_with_cross_revision_consensus_state() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let now = Timestamp::now();
+        write_client_state(&mut wl_storage, &client_id, mock_client_state(10));
+        let height = Height::new(0, 10).unwrap();
+        wl_storage
+            .write_log
+            .write(
+                &consensus_state_key(&client_id, height),
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(
+                    MockHeader {
+                        height,
+                        timestamp: now,
+                    },
+                )),
+            )
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        // the update writes a consensus state of another revision
+        let client_state = mock_client_state(11);
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(write_client_state(
+            &mut wl_storage,
+            &client_id,
+            client_state,
+        ));
+        let height = Height::new(1, 11).unwrap();
+        let key = consensus_state_key(&client_id, height);
+        wl_storage
+            .write_log
+            .write(
+                &key,
+                Protobuf::<Any>::encode_vec(MockConsensusState::new(
+                    MockHeader {
+                        height,
+                        timestamp: (now + Duration::from_secs(1)).unwrap(),
+                    },
+                )),
+            )
+            .expect("write failed");
+        keys_changed.insert(key);
+        let msg = MsgUpdateClient {
+            client_id,
+            client_message: client_state.header.into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result = ibc
+            .verify_update_client(&msg, false, &mut ClientChecks::fail_fast())
+            .unwrap_err();
+        match &result {
+            Error::ClientError(msg) => {
+                assert!(msg
+                    .starts_with("The consensus state revision doesn't match"))
+            }
+            _ => panic!("unexpected error: {result}"),
+        }
+    }
+
+    #[test]
+    fn test_validator_hash_linkage() {
+        let mut wl_storage = init_storage();
+        let client_id =
+            ClientId::new(ClientType::new("07-tendermint").unwrap(), 0)
+                .unwrap();
+        let mut keys_changed = BTreeSet::new();
+        // the adjacent states have the same root but contradictory hashes
+        for (height, next_validators_hash) in
+            [(9, [1; 32]), (10, [2; 32]), (12, [3; 32])]
+        {
+            let height = Height::new(0, height).unwrap();
+            let key = consensus_state_key(&client_id, height);
+            let consensus_state: TmConsensusState = TmConsensusStateType::new(
+                CommitmentRoot::from_bytes(&[1; 32]),
+                TmTime::now(),
+                TmHash::Sha256(next_validators_hash),
+            )
+            .into();
+            wl_storage
+                .write_log
+                .write(
+                    &key,
+                    AnyConsensusState::from(consensus_state).encode_vec(),
+                )
+                .expect("write failed");
+            keys_changed.insert(key);
+        }
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let result =
+                ibc.validate_validator_hash_linkage(&client_id).unwrap_err();
+            assert!(matches!(result, Error::HeaderError(_)));
+        }
+
+        // the state at the height 10 has another root
+        let height = Height::new(0, 10).unwrap();
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&[2; 32]),
+            TmTime::now(),
+            TmHash::Sha256([2; 32]),
+        )
+        .into();
+        wl_storage
+            .write_log
+            .write(
+                &consensus_state_key(&client_id, height),
+                AnyConsensusState::from(consensus_state).encode_vec(),
+            )
+            .expect("write failed");
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.validate_validator_hash_linkage(&client_id)
+            .expect("the validator hashes should be consistent");
+    }
+
+    #[test]
+    fn test_update_with_contradictory_adjacent_consensus_state() {
+        let mut wl_storage = init_storage();
+        let client_id =
+            ClientId::new(ClientType::new("07-tendermint").unwrap(), 0)
+                .unwrap();
+        let mut prior = tm_client_state(vec![]).inner().clone();
+        prior.latest_height = Height::new(0, 9).unwrap();
+        let prior = TmClientState::from(prior);
+        wl_storage
+            .write_log
+            .write(
+                &client_state_key(&client_id),
+                Protobuf::<Any>::encode_vec(prior),
+            )
+            .expect("write failed");
+        let now = TmTime::now();
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&[1; 32]),
+            now,
+            TmHash::Sha256([1; 32]),
+        )
+        .into();
+        wl_storage
+            .write_log
+            .write(
+                &consensus_state_key(&client_id, Height::new(0, 9).unwrap()),
+                AnyConsensusState::from(consensus_state).encode_vec(),
+            )
+            .expect("write failed");
+        wl_storage.write_log.commit_tx();
+        wl_storage.commit_block().expect("commit failed");
+
+        // the update writes the adjacent state with the same root and another
+        // validator hash
+        let mut keys_changed = BTreeSet::new();
+        let client_key = client_state_key(&client_id);
+        wl_storage
+            .write_log
+            .write(
+                &client_key,
+                Protobuf::<Any>::encode_vec(tm_client_state(vec![])),
+            )
+            .expect("write failed");
+        keys_changed.insert(client_key);
+        let consensus_key =
+            consensus_state_key(&client_id, Height::new(0, 10).unwrap());
+        let time = (now + Duration::from_secs(1)).unwrap();
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&[1; 32]),
+            time,
+            TmHash::Sha256([2; 32]),
+        )
+        .into();
+        wl_storage
+            .write_log
+            .write(
+                &consensus_key,
+                AnyConsensusState::from(consensus_state).encode_vec(),
+            )
+            .expect("write failed");
+        keys_changed.insert(consensus_key.clone());
+        let msg = MsgUpdateClient {
+            client_id: client_id.clone(),
+            client_message: mock_client_state(10).header.into(),
+            signer: "account0".to_string().into(),
+        };
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let result = ibc
+                .verify_update_client(
+                    &msg,
+                    false,
+                    &mut ClientChecks::fail_fast(),
+                )
+                .unwrap_err();
+            assert!(matches!(result, Error::HeaderError(_)));
+        }
+
+        // the written state has another root
+        let consensus_state: TmConsensusState = TmConsensusStateType::new(
+            CommitmentRoot::from_bytes(&[2; 32]),
+            time,
+            TmHash::Sha256([2; 32]),
+        )
+        .into();
+        wl_storage
+            .write_log
+            .write(
+                &consensus_key,
+                AnyConsensusState::from(consensus_state).encode_vec(),
+            )
+            .expect("write failed");
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.verify_update_client(&msg, false, &mut ClientChecks::fail_fast())
+            .expect("the update should be valid");
+    }
+
+    #[test]
+    fn test_client_view() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let mut keys_changed = BTreeSet::new();
+        keys_changed.insert(write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(10),
+        ));
+        for height in [10, 9] {
+            let height = Height::new(0, height).unwrap();
+            let key = consensus_state_key(&client_id, height);
+            let consensus_state = MockConsensusState::new(MockHeader {
+                height,
+                timestamp: Timestamp::now(),
+            });
+            wl_storage
+                .write_log
+                .write(&key, Protobuf::<Any>::encode_vec(consensus_state))
+                .expect("write failed");
+            keys_changed.insert(key);
+        }
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let view = ibc.client_view(&client_id).unwrap();
+        assert_eq!(view.client_type, Some(client_type()));
+        assert_eq!(
+            view.client_state.map(|cs| cs.latest_height()),
+            Some(Height::new(0, 10).unwrap())
+        );
+        assert_eq!(view.client_counter, Some(0));
+        assert_eq!(
+            view.consensus_heights,
+            vec![Height::new(0, 9).unwrap(), Height::new(0, 10).unwrap()]
+        );
+
+        // a client which doesn't exist
+        let view = ibc.client_view(&get_client_id(1)).unwrap();
+        assert!(view.client_type.is_none());
+        assert!(view.client_state.is_none());
+        assert!(view.consensus_heights.is_empty());
+    }
+
+    #[test]
+    fn test_timestamp_monotonicity() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(0);
+        let now = Timestamp::now();
+        let mut keys_changed = BTreeSet::new();
+        // the later height `0-10` has an earlier timestamp than `0-9`
+        for (height, timestamp) in [
+            (8, now),
+            (9, (now + Duration::from_secs(2)).unwrap()),
+            (10, (now + Duration::from_secs(1)).unwrap()),
+        ] {
+            let height = Height::new(0, height).unwrap();
+            let key = consensus_state_key(&client_id, height);
+            let consensus_state =
+                MockConsensusState::new(MockHeader { height, timestamp });
+            wl_storage
+                .write_log
+                .write(&key, Protobuf::<Any>::encode_vec(consensus_state))
+                .expect("write failed");
+            keys_changed.insert(key);
+        }
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            let result =
+                ibc.validate_timestamp_monotonicity(&client_id).unwrap_err();
+            assert!(matches!(result, Error::ClientError(_)));
+        }
+
+        let height = Height::new(0, 10).unwrap();
+        let consensus_state = MockConsensusState::new(MockHeader {
+            height,
+            timestamp: (now + Duration::from_secs(3)).unwrap(),
+        });
+        wl_storage
+            .write_log
+            .write(
+                &consensus_state_key(&client_id, height),
+                Protobuf::<Any>::encode_vec(consensus_state),
+            )
+            .expect("write failed");
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        ibc.validate_timestamp_monotonicity(&client_id)
+            .expect("the timestamps should increase");
+    }
+
+    #[test]
+    fn test_key_error() {
+        let key = client_state_key(&get_client_id(0));
+        let result = parse_consensus_height(&key).unwrap_err();
+        assert!(matches!(result, Error::KeyError { .. }));
+        assert!(result.to_string().contains(&key.to_string()));
+    }
+
+    #[test]
+    fn test_only_client_keys_changed() {
+        let mut wl_storage = init_storage();
+        let client_id = get_client_id(1);
+        let mut keys_changed = BTreeSet::new();
+        let key = write_client_state(
+            &mut wl_storage,
+            &client_id,
+            mock_client_state(10),
+        );
+        keys_changed.insert(key);
+        // the key of another client with the same prefix string
+        let other_key = write_client_state(
+            &mut wl_storage,
+            &get_client_id(10),
+            mock_client_state(10),
+        );
+
+        // a key of another module, e.g. a balance changed by the fee
+        let non_ibc_key = Key::parse("non-ibc/balance").unwrap();
+        wl_storage
+            .write_log
+            .write(&non_ibc_key, vec![1])
+            .expect("write failed");
+        keys_changed.insert(non_ibc_key);
+
+        let tx = Tx::from_type(TxType::Raw);
+        let verifiers = BTreeSet::new();
+        {
+            let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+            let ibc = Ibc { ctx };
+            ibc.assert_only_client_keys_changed(&client_id)
+                .expect("only the client keys should be changed in IBC");
+        }
+
+        keys_changed.insert(other_key);
+        let ctx = setup_ctx(&tx, &wl_storage, &keys_changed, &verifiers);
+        let ibc = Ibc { ctx };
+        let result =
+            ibc.assert_only_client_keys_changed(&client_id).unwrap_err();
+        assert!(matches!(result, Error::StateChange(_)));
+    }
+}
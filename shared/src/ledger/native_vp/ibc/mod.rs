@@ -1,9 +1,10 @@
 //! IBC integration as a native validity predicate
 
+pub mod client;
 pub mod context;
 
 use std::cell::RefCell;
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::rc::Rc;
 use std::time::Duration;
 
@@ -17,7 +18,7 @@ use namada_core::ledger::ibc::{
 use namada_core::ledger::storage::write_log::StorageModification;
 use namada_core::ledger::storage::{self as ledger_storage, StorageHasher};
 use namada_core::proto::Tx;
-use namada_core::types::address::Address;
+use namada_core::types::address::{Address, InternalAddress};
 use namada_core::types::storage::Key;
 use namada_proof_of_stake::storage::read_pos_params;
 use thiserror::Error;
@@ -26,6 +27,7 @@ use crate::ibc::core::host::types::identifiers::ChainId as IbcChainId;
 use crate::ledger::ibc::storage::{calc_hash, is_ibc_denom_key, is_ibc_key};
 use crate::ledger::native_vp::{self, Ctx, NativeVp, VpEnv};
 use crate::ledger::parameters::read_epoch_duration_parameter;
+use crate::ledger::storage_api;
 use crate::vm::WasmCacheAccess;
 
 #[allow(missing_docs)]
@@ -43,11 +45,72 @@ pub enum Error {
     StateChange(String),
     #[error("IBC event error: {0}")]
     IbcEvent(String),
+    #[error("Client error: {0}")]
+    ClientError(String),
+    #[error("Header error: {0}")]
+    HeaderError(String),
+    #[error("Proof verification error: {0}")]
+    ProofVerificationError(String),
+    #[error("IBC data error: {0}")]
+    IbcDataError(String),
+    #[error("Key error: Key {key}, Reason {reason}")]
+    KeyError { key: String, reason: String },
+    #[error("Unsupported version error: {0}")]
+    UnsupportedVersion(String),
+}
+
+impl Error {
+    /// Returns a stable code identifying the category of the error, so that
+    /// clients can react to failures without parsing the messages.
+    ///
+    /// | Code | Variant                  |
+    /// |------|--------------------------|
+    /// | 1    | `NativeVpError`          |
+    /// | 2    | `Decoding`               |
+    /// | 3    | `NoTxData`               |
+    /// | 4    | `IbcAction`              |
+    /// | 5    | `StateChange`            |
+    /// | 6    | `IbcEvent`               |
+    /// | 7    | `ClientError`            |
+    /// | 8    | `HeaderError`            |
+    /// | 9    | `ProofVerificationError` |
+    /// | 10   | `IbcDataError`           |
+    /// | 11   | `KeyError`               |
+    /// | 12   | `UnsupportedVersion`     |
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::NativeVpError(_) => 1,
+            Self::Decoding(_) => 2,
+            Self::NoTxData => 3,
+            Self::IbcAction(_) => 4,
+            Self::StateChange(_) => 5,
+            Self::IbcEvent(_) => 6,
+            Self::ClientError(_) => 7,
+            Self::HeaderError(_) => 8,
+            Self::ProofVerificationError(_) => 9,
+            Self::IbcDataError(_) => 10,
+            Self::KeyError { .. } => 11,
+            Self::UnsupportedVersion(_) => 12,
+        }
+    }
 }
 
 /// IBC functions result
 pub type VpResult<T> = std::result::Result<T, Error>;
 
+/// The transition of the value of a key by a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateChange {
+    /// The value doesn't exist in the prior state
+    Created,
+    /// The value exists in both states and is changed
+    Updated,
+    /// The value doesn't exist in the posterior state
+    Deleted,
+    /// The value is the same in both states, or doesn't exist in either
+    NotChanged,
+}
+
 /// IBC VP
 pub struct Ibc<'a, DB, H, CA>
 where
@@ -82,6 +145,11 @@ where
         // Validate the state according to the given IBC message
         self.validate_with_msg(&tx_data)?;
 
+        // Validate the client changes according to the given client message,
+        // only when the client checks are enabled as a consensus rule
+        #[cfg(feature = "ibc-client-validation")]
+        self.validate_client(&tx_data)?;
+
         // Validate the denom store if a denom key has been changed
         self.validate_denom(keys_changed)?;
 
@@ -180,6 +248,62 @@ where
         })
     }
 
+    /// Classify the change of the value of the IBC key by the transaction,
+    /// comparing the prior and the posterior values
+    pub fn classify_change(&self, key: &Key) -> VpResult<StateChange> {
+        if !is_ibc_key(key) {
+            return Err(Error::KeyError {
+                key: key.to_string(),
+                reason: "The key isn't an IBC key".to_string(),
+            });
+        }
+        let prior =
+            self.ctx.read_bytes_pre(key).map_err(Error::NativeVpError)?;
+        let posterior = self
+            .ctx
+            .read_bytes_post(key)
+            .map_err(Error::NativeVpError)?;
+        Ok(match (prior, posterior) {
+            (None, Some(_)) => StateChange::Created,
+            (Some(prior), Some(posterior)) if prior != posterior => {
+                StateChange::Updated
+            }
+            (Some(_), None) => StateChange::Deleted,
+            _ => StateChange::NotChanged,
+        })
+    }
+
+    /// Get all the IBC keys whose values differ between the prior and the
+    /// posterior states, including the deleted keys, in the key order.
+    ///
+    /// Unlike the changed keys given to the VP, this diffs the values, so a
+    /// key rewritten with the same value isn't listed.
+    pub fn changed_ibc_keys(&self) -> VpResult<Vec<Key>> {
+        let prefix =
+            Key::from(Address::Internal(InternalAddress::Ibc).to_db_key());
+        let pre = self.ctx.pre();
+        let mut prior = BTreeMap::new();
+        for result in storage_api::iter_prefix_bytes(&pre, &prefix)
+            .map_err(Error::NativeVpError)?
+        {
+            let (key, value) = result.map_err(Error::NativeVpError)?;
+            prior.insert(key, value);
+        }
+        let post = self.ctx.post();
+        let mut changed = BTreeSet::new();
+        for result in storage_api::iter_prefix_bytes(&post, &prefix)
+            .map_err(Error::NativeVpError)?
+        {
+            let (key, value) = result.map_err(Error::NativeVpError)?;
+            if prior.remove(&key).as_ref() != Some(&value) {
+                changed.insert(key);
+            }
+        }
+        // The remaining keys don't exist in the posterior state
+        changed.extend(prior.into_keys());
+        Ok(changed.into_iter().collect())
+    }
+
     fn validate_denom(&self, keys_changed: &BTreeSet<Key>) -> VpResult<()> {
         for key in keys_changed {
             if let Some((_, hash)) = is_ibc_denom_key(key) {
@@ -2739,4 +2863,180 @@ mod tests {
                 .expect("validation failed")
         );
     }
+
+    #[test]
+    fn test_classify_change() {
+        let mut wl_storage = init_storage();
+        insert_init_client(&mut wl_storage);
+        let client_id = get_client_id();
+        let mut keys_changed = BTreeSet::new();
+        // create a connection
+        let conn_key = connection_key(&get_connection_id());
+        let bytes = get_connection(ConnState::Init).encode_vec();
+        wl_storage
+            .write_log
+            .write(&conn_key, bytes)
+            .expect("write failed");
+        keys_changed.insert(conn_key.clone());
+        // update the client counter
+        let counter_key = client_counter_key();
+        wl_storage
+            .write_log
+            .write(&counter_key, 1u64.serialize_to_vec())
+            .expect("write failed");
+        keys_changed.insert(counter_key.clone());
+        // delete the consensus state
+        let consensus_key =
+            consensus_state_key(&client_id, Height::new(0, 1).unwrap());
+        wl_storage
+            .write_log
+            .delete(&consensus_key)
+            .expect("delete failed");
+        keys_changed.insert(consensus_key.clone());
+        // rewrite the same client state
+        let client_state_key = client_state_key(&client_id);
+        let bytes = wl_storage
+            .read_bytes(&client_state_key)
+            .expect("read failed")
+            .expect("the client state should exist");
+        wl_storage
+            .write_log
+            .write(&client_state_key, bytes)
+            .expect("write failed");
+        keys_changed.insert(client_state_key.clone());
+
+        let tx_index = TxIndex::default();
+        let tx = Tx::from_type(TxType::Raw);
+        let gas_meter = VpGasMeter::new_from_tx_meter(
+            &TxGasMeter::new_from_sub_limit(TX_GAS_LIMIT.into()),
+        );
+        let (vp_wasm_cache, _vp_cache_dir) =
+            wasm::compilation_cache::common::testing::cache();
+        let verifiers = BTreeSet::new();
+        let ctx = Ctx::new(
+            &ADDRESS,
+            &wl_storage.storage,
+            &wl_storage.write_log,
+            &tx,
+            &tx_index,
+            gas_meter,
+            &keys_changed,
+            &verifiers,
+            vp_wasm_cache,
+        );
+        let ibc = Ibc { ctx };
+
+        assert_eq!(
+            ibc.classify_change(&conn_key).unwrap(),
+            StateChange::Created
+        );
+        assert_eq!(
+            ibc.classify_change(&counter_key).unwrap(),
+            StateChange::Updated
+        );
+        assert_eq!(
+            ibc.classify_change(&consensus_key).unwrap(),
+            StateChange::Deleted
+        );
+        assert_eq!(
+            ibc.classify_change(&client_state_key).unwrap(),
+            StateChange::NotChanged
+        );
+        let non_ibc_key = balance_key(&nam(), &established_address_1());
+        let result = ibc.classify_change(&non_ibc_key).unwrap_err();
+        assert_matches!(result, Error::KeyError { .. });
+    }
+
+    #[test]
+    fn test_changed_ibc_keys() {
+        let mut wl_storage = init_storage();
+        insert_init_client(&mut wl_storage);
+        let client_id = get_client_id();
+        let mut keys_changed = BTreeSet::new();
+        // update the client to the height 2
+        let height = Height::new(0, 2).unwrap();
+        let header = MockHeader {
+            height,
+            timestamp: Timestamp::now(),
+        };
+        let client_state_key = client_state_key(&client_id);
+        let bytes = Protobuf::<Any>::encode_vec(MockClientState::new(header));
+        wl_storage
+            .write_log
+            .write(&client_state_key, bytes)
+            .expect("write failed");
+        keys_changed.insert(client_state_key.clone());
+        let consensus_key = consensus_state_key(&client_id, height);
+        let bytes =
+            Protobuf::<Any>::encode_vec(MockConsensusState::new(header));
+        wl_storage
+            .write_log
+            .write(&consensus_key, bytes)
+            .expect("write failed");
+        keys_changed.insert(consensus_key.clone());
+        // rewrite the same client counter
+        let counter_key = client_counter_key();
+        let bytes = wl_storage
+            .read_bytes(&counter_key)
+            .expect("read failed")
+            .expect("the counter should exist");
+        wl_storage
+            .write_log
+            .write(&counter_key, bytes)
+            .expect("write failed");
+        keys_changed.insert(counter_key);
+
+        let tx_index = TxIndex::default();
+        let tx = Tx::from_type(TxType::Raw);
+        let gas_meter = VpGasMeter::new_from_tx_meter(
+            &TxGasMeter::new_from_sub_limit(TX_GAS_LIMIT.into()),
+        );
+        let (vp_wasm_cache, _vp_cache_dir) =
+            wasm::compilation_cache::common::testing::cache();
+        let verifiers = BTreeSet::new();
+        let ctx = Ctx::new(
+            &ADDRESS,
+            &wl_storage.storage,
+            &wl_storage.write_log,
+            &tx,
+            &tx_index,
+            gas_meter,
+            &keys_changed,
+            &verifiers,
+            vp_wasm_cache,
+        );
+        let ibc = Ibc { ctx };
+
+        let changed: BTreeSet<Key> =
+            ibc.changed_ibc_keys().unwrap().into_iter().collect();
+        let expected: BTreeSet<Key> =
+            [client_state_key, consensus_key].into_iter().collect();
+        assert_eq!(changed, expected);
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let errors = [
+            Error::NativeVpError(native_vp::Error::new_const("error")),
+            Error::Decoding(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "error",
+            )),
+            Error::NoTxData,
+            Error::IbcAction(ActionError::DecodingData),
+            Error::StateChange("error".to_string()),
+            Error::IbcEvent("error".to_string()),
+            Error::ClientError("error".to_string()),
+            Error::HeaderError("error".to_string()),
+            Error::ProofVerificationError("error".to_string()),
+            Error::IbcDataError("error".to_string()),
+            Error::KeyError {
+                key: "key".to_string(),
+                reason: "error".to_string(),
+            },
+            Error::UnsupportedVersion("error".to_string()),
+        ];
+        let codes: HashSet<u32> = errors.iter().map(Error::code).collect();
+        assert_eq!(codes.len(), errors.len());
+    }
 }